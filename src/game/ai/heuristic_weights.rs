@@ -0,0 +1,119 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HeuristicWeights {
+    pub attack: f32, // per own symbol in winnable distance
+    pub defense: f32, // per opponent symbol in winnable distance
+    pub block_bonus: f32, // multiplies the defense when the move blocks a long enough opponent sequence
+    pub available_axis: f32,
+    pub empty_around: f32,
+    pub threat: f32, // per square the move leaves one move from winning, so forks count twice
+    pub overall: f32 // applied to the sum of every other term
+}
+
+#[derive(Debug)]
+pub enum WeightsError {
+    Io(std::io::Error),
+    InvalidLine(usize), // line numbers start at 1
+    UnknownWeight(usize, String),
+    InvalidValue(usize, String)
+}
+
+impl Default for HeuristicWeights {
+    fn default() -> Self {
+        HeuristicWeights {
+            attack: 1.0,
+            defense: 0.1,
+            block_bonus: 100.0,
+            available_axis: 0.1,
+            empty_around: 0.01,
+            threat: 2.0,
+            overall: 0.01
+        }
+    }
+}
+
+impl HeuristicWeights {
+    pub const NAMES: [&'static str; 7] = ["attack", "defense", "block_bonus", "available_axis", "empty_around", "threat", "overall"];
+
+    pub fn load(path: impl AsRef<Path>) -> Result<HeuristicWeights, WeightsError> {
+        std::fs::read_to_string(path).map_err(WeightsError::Io)?.parse()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), WeightsError> {
+        std::fs::write(path, self.to_string()).map_err(WeightsError::Io)
+    }
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        match name {
+            "attack" => Some(self.attack),
+            "defense" => Some(self.defense),
+            "block_bonus" => Some(self.block_bonus),
+            "available_axis" => Some(self.available_axis),
+            "empty_around" => Some(self.empty_around),
+            "threat" => Some(self.threat),
+            "overall" => Some(self.overall),
+            _ => None
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut f32> {
+        match name {
+            "attack" => Some(&mut self.attack),
+            "defense" => Some(&mut self.defense),
+            "block_bonus" => Some(&mut self.block_bonus),
+            "available_axis" => Some(&mut self.available_axis),
+            "empty_around" => Some(&mut self.empty_around),
+            "threat" => Some(&mut self.threat),
+            "overall" => Some(&mut self.overall),
+            _ => None
+        }
+    }
+}
+
+impl FromStr for HeuristicWeights {
+    type Err = WeightsError;
+
+    // one "name = value" per line, missing weights keep their default and # starts a comment
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = HeuristicWeights::default();
+        for (n, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue
+            }
+            let (name, value) = line.split_once('=').ok_or(WeightsError::InvalidLine(n + 1))?;
+            let (name, value) = (name.trim(), value.trim());
+            let weight = weights.get_mut(name).ok_or_else(|| WeightsError::UnknownWeight(n + 1, name.to_string()))?;
+            *weight = value.parse().map_err(|_| WeightsError::InvalidValue(n + 1, value.to_string()))?;
+        }
+        Ok(weights)
+    }
+}
+
+impl Display for HeuristicWeights {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, name) in HeuristicWeights::NAMES.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} = {}", name, self.get(name).unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for WeightsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeightsError::Io(e) => write!(f, "Couldn't access the weights file: {}", e),
+            WeightsError::InvalidLine(n) => write!(f, "Line {} should look like \"name = value\"", n),
+            WeightsError::UnknownWeight(n, name) => write!(f, "Line {} has an unknown weight \"{}\", the known ones are: {}", n, name, HeuristicWeights::NAMES.join(", ")),
+            WeightsError::InvalidValue(n, value) => write!(f, "Line {} has an invalid number \"{}\"", n, value),
+        }
+    }
+}
+
+impl std::error::Error for WeightsError {}
\ No newline at end of file
@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use crate::game::ai::node::Node;
+use crate::game::ai::search_stats::SearchStats;
+use crate::game::tictactoe_core::{SquareState, TicTacToe, TurnState};
+
+pub const DEFAULT_ITERATIONS: usize = 10000;
+const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+#[derive(Clone, PartialEq)]
+struct MctsData {
+    move_index: usize,
+    mover: char, // symbol of who played the move that leads to this node
+    visits: u32,
+    wins: f64, // draws count as half a win
+    untried_moves: Vec<usize>,
+    result: Option<TurnState> // set when the move ended the game
+}
+
+#[derive(Clone)]
+pub struct MctsPlayer {
+    iterations: Option<usize>,
+    time_limit: Option<Duration>,
+    exploration: f64,
+    seed: u64,
+    pub symbol: char,
+    pub op_symbol: char,
+    last_search: SearchStats
+}
+
+impl MctsPlayer {
+    pub fn create(iterations: Option<usize>, time_limit: Option<Duration>, symbol: char, op_symbol: char, seed: u64) -> MctsPlayer {
+        MctsPlayer {
+            iterations,
+            time_limit,
+            exploration: DEFAULT_EXPLORATION,
+            seed,
+            symbol,
+            op_symbol,
+            last_search: SearchStats::default()
+        }
+    }
+    pub fn set_limits(&mut self, iterations: Option<usize>, time_limit: Option<Duration>) { // with neither, DEFAULT_ITERATIONS is used
+        self.iterations = iterations;
+        self.time_limit = time_limit
+    }
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed
+    }
+    pub fn last_search_stats(&self) -> &SearchStats {
+        &self.last_search
+    }
+    pub fn act(&mut self, current_board: TicTacToe, time_budget: Option<Duration>) -> (usize, usize) {
+        let start = Instant::now();
+        let time_limit = time_budget.or(self.time_limit);
+        // the same seed and position always give the same move, no matter how many moves were made before
+        let mut rng = StdRng::seed_from_u64(self.seed ^ current_board.position_hash());
+        let mut root = Node::new(MctsData {
+            move_index: 0,
+            mover: self.op_symbol,
+            visits: 0,
+            wins: 0.0,
+            untried_moves: MctsPlayer::empty_squares(&current_board),
+            result: None
+        });
+        root.data.untried_moves.shuffle(&mut rng);
+        let max_iterations = if self.iterations.is_none() && time_limit.is_none() { Some(DEFAULT_ITERATIONS) } else { self.iterations };
+        let mut iterations = 0;
+        let mut max_depth = 0;
+        loop {
+            if max_iterations.is_some_and(|max| iterations >= max) || time_limit.is_some_and(|limit| start.elapsed() >= limit) {
+                break
+            }
+            let mut board = current_board.clone();
+            let (_, depth) = self.iterate(&mut root, &mut board, &mut rng);
+            max_depth = max_depth.max(depth);
+            iterations += 1;
+        }
+        let best = root.children.iter().max_by_key(|child| child.data.visits);
+        let index = match best {
+            Some(child) => child.data.move_index,
+            None => root.data.untried_moves[0]
+        };
+        self.last_search = SearchStats {
+            depth_reached: max_depth,
+            max_depth,
+            nodes: iterations,
+            search_time: start.elapsed(),
+            time_budget: time_limit,
+            principal_variation: MctsPlayer::principal_variation(&root, &current_board),
+            ..Default::default()
+        };
+        current_board.get_index_coord(index)
+    }
+
+    fn principal_variation(root: &Node<MctsData>, board: &TicTacToe) -> Vec<(usize, usize)> { // the most visited path
+        let mut variation = vec![];
+        let mut node = root;
+        while let Some(child) = node.children.iter().max_by_key(|child| child.data.visits) {
+            variation.push(board.get_index_coord(child.data.move_index));
+            node = child;
+        }
+        variation
+    }
+
+    fn empty_squares(board: &TicTacToe) -> Vec<usize> {
+        (0..board.size()).filter(|i| board.get_square_by_index(*i) == Some(&SquareState::None)).collect()
+    }
+
+    fn other_symbol(&self, symbol: char) -> char {
+        if symbol == self.symbol { self.op_symbol } else { self.symbol }
+    }
+
+    // selection, expansion, rollout and backpropagation of one iteration, returns the winner and the depth reached
+    fn iterate(&self, node: &mut Node<MctsData>, board: &mut TicTacToe, rng: &mut StdRng) -> (Option<char>, usize) {
+        let (winner, depth) = if let Some(result) = &node.data.result {
+            (if let TurnState::Victory(_) = result { Some(node.data.mover) } else { None }, 0)
+        }else if let Some(index) = node.data.untried_moves.pop() {
+            let mover = self.other_symbol(node.data.mover);
+            let state = board.set_square_from_index(index, SquareState::Filled(mover)).unwrap();
+            let mut untried_moves = if state == TurnState::Continue { MctsPlayer::empty_squares(board) } else { vec![] };
+            untried_moves.shuffle(rng);
+            let mut child = Node::new(MctsData { move_index: index, mover, visits: 0, wins: 0.0, untried_moves, result: None });
+            let winner = match state {
+                TurnState::Continue => self.rollout(board, self.other_symbol(mover), rng),
+                TurnState::Victory(_) => Some(mover),
+                TurnState::Draw => None
+            };
+            if state != TurnState::Continue {
+                child.data.result = Some(state);
+            }
+            MctsPlayer::backpropagate(&mut child, winner);
+            node.children.push(child);
+            (winner, 1)
+        }else {
+            let parent_visits = (node.data.visits as f64).ln();
+            let exploration = self.exploration;
+            let child = node.children.iter_mut().max_by(|a, b| {
+                MctsPlayer::uct(&a.data, parent_visits, exploration).total_cmp(&MctsPlayer::uct(&b.data, parent_visits, exploration))
+            }).unwrap();
+            board.set_square_from_index(child.data.move_index, SquareState::Filled(child.data.mover)).unwrap();
+            let (winner, depth) = self.iterate(child, board, rng);
+            (winner, depth + 1)
+        };
+        MctsPlayer::backpropagate(node, winner);
+        (winner, depth)
+    }
+
+    fn uct(data: &MctsData, ln_parent_visits: f64, exploration: f64) -> f64 {
+        let visits = data.visits as f64;
+        data.wins / visits + exploration * (ln_parent_visits / visits).sqrt()
+    }
+
+    fn backpropagate(node: &mut Node<MctsData>, winner: Option<char>) {
+        node.data.visits += 1;
+        match winner {
+            Some(symbol) if symbol == node.data.mover => node.data.wins += 1.0,
+            None => node.data.wins += 0.5,
+            _ => {}
+        }
+    }
+
+    fn rollout(&self, board: &mut TicTacToe, mut to_move: char, rng: &mut StdRng) -> Option<char> {
+        let mut empty_squares = MctsPlayer::empty_squares(board);
+        empty_squares.shuffle(rng);
+        for index in empty_squares {
+            match board.set_square_from_index(index, SquareState::Filled(to_move)) {
+                Ok(TurnState::Victory(_)) => return Some(to_move),
+                Ok(TurnState::Draw) => return None,
+                _ => to_move = self.other_symbol(to_move)
+            }
+        }
+        None
+    }
+}
@@ -1,2 +1,12 @@
 mod node;
+pub mod transposition_table;
+pub mod search_stats;
+pub mod solver;
+pub mod mcts_player;
+pub mod heuristic_weights;
+pub mod tuner;
+pub mod opening_book;
+pub mod tablebase;
+pub mod proof_number;
+pub mod threat_space;
 pub mod tictactoe_ai_player;
\ No newline at end of file
@@ -4,7 +4,7 @@ use std::cmp::Ordering;
 pub struct Node<T> {
     pub data: T,
     pub children: Vec<Node<T>>,
-    pub data_score: f32
+    pub data_score: i32
 }
 
 impl<T> Node<T> {
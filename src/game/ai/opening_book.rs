@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use rand::Rng;
+use crate::game::ai::tictactoe_ai_player::{Ai, Score};
+use crate::game::symmetry::BoardSymmetries;
+use crate::game::tictactoe_core::{SquareState, TicTacToe, TurnState};
+
+const BOOK_REPLIES: usize = 3; // most replies kept for a position
+const BOOK_MARGIN: Score = 1000; // replies scoring further than this below the best one are left out
+const BOOK_MOVES_TO_ANALYZE: usize = 10;
+
+/*
+THE BOOK FILE LOOKS LIKE:
+    board 3 3 3                 <- width, height and sequence to win
+    x---o---- 8:3 2:1           <- position followed by replies as square:weight
+Positions are written from the point of view of the side to move (x) against the other side (o),
+in the orientation that gives the smallest text among every symmetry of the board, replies use that orientation too.
+*/
+pub struct OpeningBook {
+    pub x_size: usize,
+    pub y_size: usize,
+    pub seq_to_win: usize,
+    positions: HashMap<String, Vec<(usize, u32)>>,
+    symmetries: BoardSymmetries
+}
+
+#[derive(Debug)]
+pub enum BookError {
+    Io(std::io::Error),
+    MissingBoard,
+    InvalidLine(usize), // line numbers start at 1
+    WrongBoard(usize, usize, usize) // width, height and sequence to win the book was made for
+}
+
+impl OpeningBook {
+    pub fn new(x_size: usize, y_size: usize, seq_to_win: usize) -> OpeningBook {
+        OpeningBook {
+            x_size,
+            y_size,
+            seq_to_win,
+            positions: HashMap::new(),
+            symmetries: BoardSymmetries::new(x_size, y_size)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn fits(&self, board: &TicTacToe) -> bool {
+        board.x_size == self.x_size && board.y_size == self.y_size && board.seq_to_win == self.seq_to_win
+    }
+
+    // the text of the position and the symmetry that turns the board into it
+    fn normalize(&self, board: &TicTacToe, symbol: char) -> (String, usize) {
+        let labels: Vec<char> = board.squares.iter().map(|square| match square {
+            SquareState::Filled(c) if *c == symbol => 'x',
+            SquareState::Filled(_) => 'o',
+            SquareState::None => '-'
+        }).collect();
+        (0..self.symmetries.len()).map(|symmetry| {
+            let mut cells = vec!['-'; labels.len()];
+            for (i, label) in labels.iter().enumerate() {
+                cells[self.symmetries.map_index(symmetry, i)] = *label;
+            }
+            (cells.into_iter().collect::<String>(), symmetry)
+        }).min().unwrap()
+    }
+
+    pub fn probe(&self, board: &TicTacToe, symbol: char) -> Option<Vec<(usize, u32)>> { // replies for the symbol to move, as board indexes
+        if !self.fits(board) {
+            return None
+        }
+        let (key, symmetry) = self.normalize(board, symbol);
+        let replies = self.positions.get(&key)?;
+        Some(replies.iter().map(|(index, weight)| (self.symmetries.unmap_index(symmetry, *index), *weight)).collect())
+    }
+
+    pub fn pick(&self, board: &TicTacToe, symbol: char, rng: &mut impl Rng) -> Option<usize> {
+        let replies = self.probe(board, symbol)?;
+        let total: u32 = replies.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None
+        }
+        let mut roll = rng.gen_range(0..total);
+        for (index, weight) in replies {
+            if roll < weight {
+                return Some(index)
+            }
+            roll -= weight
+        }
+        None
+    }
+
+    pub fn insert(&mut self, board: &TicTacToe, symbol: char, replies: &[(usize, u32)]) {
+        let (key, symmetry) = self.normalize(board, symbol);
+        let replies = replies.iter().map(|(index, weight)| (self.symmetries.map_index(symmetry, *index), *weight)).collect();
+        self.positions.insert(key, replies);
+    }
+
+    pub fn load(path: impl AsRef<Path>, board: &TicTacToe) -> Result<OpeningBook, BookError> { // only a book made for the board loads
+        let text = std::fs::read_to_string(path).map_err(BookError::Io)?;
+        let mut book: Option<OpeningBook> = None;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue
+            }
+            let mut words = line.split_whitespace();
+            let first = words.next().unwrap();
+            if first == "board" {
+                let sizes: Vec<usize> = words.map(|word| word.parse().map_err(|_| BookError::InvalidLine(n + 1))).collect::<Result<_, _>>()?;
+                match sizes[..] {
+                    [x, y, seq] if x > 0 && y > 0 && TicTacToe::fits_bitboard(x, y) => book = Some(OpeningBook::new(x, y, seq)),
+                    _ => return Err(BookError::InvalidLine(n + 1))
+                }
+                continue
+            }
+            let book = book.as_mut().ok_or(BookError::MissingBoard)?;
+            let size = book.x_size * book.y_size;
+            if first.chars().count() != size || first.chars().any(|c| !"xo-".contains(c)) {
+                return Err(BookError::InvalidLine(n + 1))
+            }
+            let mut replies = vec![];
+            for word in words {
+                let reply = word.split_once(':').and_then(|(index, weight)| Some((index.parse().ok()?, weight.parse().ok()?)));
+                match reply {
+                    Some((index, weight)) if index < size => replies.push((index, weight)),
+                    _ => return Err(BookError::InvalidLine(n + 1))
+                }
+            }
+            book.positions.insert(first.to_string(), replies);
+        }
+        let book = book.ok_or(BookError::MissingBoard)?;
+        if !book.fits(board) {
+            return Err(BookError::WrongBoard(book.x_size, book.y_size, book.seq_to_win))
+        }
+        Ok(book)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BookError> {
+        std::fs::write(path, self.to_string()).map_err(BookError::Io)
+    }
+
+    // searches every position up to the given amount of moves, following every move the search would consider for both sides
+    pub fn generate(x_size: usize, y_size: usize, seq_to_win: usize, plies: usize, depth: usize, seed: u64) -> OpeningBook {
+        let mut book = OpeningBook::new(x_size, y_size, seq_to_win);
+        let mut positions = vec![TicTacToe::new(x_size, y_size, seq_to_win, ' ')];
+        for ply in 0..plies {
+            let (symbol, op_symbol) = if ply % 2 == 0 { ('X', 'O') } else { ('O', 'X') };
+            let mut ai = Ai::create(BOOK_MOVES_TO_ANALYZE, depth, symbol, op_symbol);
+            ai.set_seed(seed);
+            let mut next_positions: HashMap<String, TicTacToe> = HashMap::new();
+            for (i, board) in positions.iter().enumerate() {
+                let ranking = ai.rank_moves(board, BOOK_REPLIES, None);
+                let best = ranking.first().map_or(0, |(_, score)| *score);
+                let replies: Vec<(usize, u32)> = ranking.iter().enumerate().filter(|(_, (_, score))| *score >= best - BOOK_MARGIN)
+                    .map(|(rank, ((x, y), _))| (x + y * x_size, (BOOK_REPLIES - rank) as u32)).collect();
+                book.insert(board, symbol, &replies);
+                println!("Move {}: position {} of {}, best reply scored {}", ply + 1, i + 1, positions.len(), best);
+                if ply + 1 == plies {
+                    continue
+                }
+                let moves = if board.filled == 0 {
+                    (0..board.size()).collect()
+                }else {
+                    board.candidate_moves(ai.candidate_radius())
+                };
+                for index in moves {
+                    let mut next = board.clone();
+                    if let Ok(TurnState::Continue) = next.set_square_from_index(index, SquareState::Filled(symbol)) {
+                        next_positions.entry(book.normalize(&next, op_symbol).0).or_insert(next);
+                    }
+                }
+            }
+            positions = next_positions.into_values().collect();
+            positions.sort_by_key(|board| board.position_hash()); // the same seed gives the same book
+        }
+        book
+    }
+}
+
+impl Display for OpeningBook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "board {} {} {}", self.x_size, self.y_size, self.seq_to_win)?;
+        let mut keys: Vec<&String> = self.positions.keys().collect();
+        keys.sort_by_key(|key| (key.chars().filter(|c| *c != '-').count(), *key));
+        for key in keys {
+            write!(f, "\n{}", key)?;
+            for (index, weight) in &self.positions[key] {
+                write!(f, " {}:{}", index, weight)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for BookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookError::Io(e) => write!(f, "Couldn't access the book file: {}", e),
+            BookError::MissingBoard => write!(f, "The book doesn't start with a \"board <width> <height> <sequence to win>\" line"),
+            BookError::InvalidLine(n) => write!(f, "Line {} of the book is invalid", n),
+            BookError::WrongBoard(x, y, seq) => write!(f, "The book was made for a {}x{} board with {} in a row, not for this one", x, y, seq),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with(x_size: usize, y_size: usize, seq_to_win: usize, moves: &[(usize, usize, char)]) -> TicTacToe {
+        let mut board = TicTacToe::new(x_size, y_size, seq_to_win, ' ');
+        for (x, y, symbol) in moves {
+            board.set_square(*x, *y, SquareState::Filled(*symbol)).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn saved_books_load_the_same_and_only_for_their_board() {
+        let path = std::env::temp_dir().join(format!("book_test_{}.txt", std::process::id()));
+        let mut book = OpeningBook::new(4, 3, 3);
+        book.insert(&board_with(4, 3, 3, &[]), 'X', &[(5, 3), (6, 2)]);
+        book.insert(&board_with(4, 3, 3, &[(1, 1, 'X')]), 'O', &[(2, 1), (0, 1)]);
+        book.insert(&board_with(4, 3, 3, &[(1, 1, 'X'), (2, 1, 'O')]), 'X', &[(1, 3)]);
+        book.save(&path).unwrap();
+        let loaded = OpeningBook::load(&path, &board_with(4, 3, 3, &[]));
+        let wrong_size = OpeningBook::load(&path, &board_with(3, 4, 3, &[]));
+        let wrong_sequence = OpeningBook::load(&path, &board_with(4, 3, 4, &[]));
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.to_string(), book.to_string());
+        let board = board_with(4, 3, 3, &[(1, 1, 'X')]);
+        assert_eq!(loaded.probe(&board, 'O'), book.probe(&board, 'O'));
+        assert!(matches!(wrong_size, Err(BookError::WrongBoard(4, 3, 3))));
+        assert!(matches!(wrong_sequence, Err(BookError::WrongBoard(4, 3, 3))));
+    }
+
+    #[test]
+    fn probes_find_the_position_turned_around() {
+        let mut book = OpeningBook::new(3, 3, 3);
+        book.insert(&board_with(3, 3, 3, &[(0, 0, 'X'), (1, 0, 'O')]), 'X', &[(8, 1)]); // (2, 2)
+        let turned = board_with(3, 3, 3, &[(2, 0, 'X'), (2, 1, 'O')]); // turned a quarter clockwise
+        assert_eq!(book.probe(&turned, 'X'), Some(vec![(6, 1)])); // (0, 2)
+        let mirrored = board_with(3, 3, 3, &[(0, 2, 'X'), (1, 2, 'O')]); // upside down
+        assert_eq!(book.probe(&mirrored, 'X'), Some(vec![(2, 1)])); // (2, 0)
+        assert_eq!(book.probe(&mirrored, 'O'), None); // the other side to move
+    }
+}
\ No newline at end of file
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+use crate::game::tictactoe_core::{SquareState, TicTacToe, TurnState};
+
+pub const DEFAULT_MAX_NODES: usize = 2_000_000;
+const INFINITY: u32 = u32::MAX;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ProofResult {
+    Proven, // the side to move can force a win
+    Disproven, // it can't, the best it can get is a draw
+    Unknown // the node or time limit was reached first
+}
+
+impl Display for ProofResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofResult::Proven => write!(f, "Proven, the side to move can force a win"),
+            ProofResult::Disproven => write!(f, "Disproven, the side to move can't force a win"),
+            ProofResult::Unknown => write!(f, "Unknown, the search ran out of nodes or time"),
+        }
+    }
+}
+
+// the children of a node are next to each other in the tree, so a node only keeps where they start
+#[derive(Copy, Clone)]
+struct ProofNode {
+    move_index: usize,
+    parent: usize, // the root is its own parent
+    first_child: usize,
+    children: usize, // 0 until the node gets expanded
+    proof: u32, // least amount of leaves that still have to be proven to prove the node
+    disproof: u32
+}
+
+/*
+THE SEARCH ALTERNATES BETWEEN:
+    - Going down from the root to the most proving leaf, the one that helps the most to either prove or disprove the root
+    - Expanding that leaf, every move of a finished game is already proven or disproven
+    - Going back up to the root updating the numbers, the side to move (OR) needs one proven child, the other side (AND) needs all of them
+A move that leaves the other side a win it can't stop, or a position where the side trying to win has no free line left,
+is settled without going deeper.
+Only the moves of the side trying to win can be limited to a radius, every defence has to be tried for the proof to hold.
+*/
+pub struct ProofNumberSearch {
+    pub max_nodes: usize,
+    pub time_limit: Option<Duration>,
+    pub candidate_radius: usize, // 0 tries every empty square, otherwise a disproof only holds for the squares tried
+    pub nodes: usize,
+    tree: Vec<ProofNode>,
+    solved: HashMap<u64, usize> // the node that first proved or disproved a position, since the tree doesn't join moves that lead to the same one
+}
+
+impl ProofNumberSearch {
+    pub fn new(max_nodes: usize) -> ProofNumberSearch {
+        ProofNumberSearch {
+            max_nodes,
+            time_limit: None,
+            candidate_radius: 0,
+            nodes: 0,
+            tree: vec![],
+            solved: HashMap::new()
+        }
+    }
+
+    // a position written like in the opening book, row by row with x and o for the players and - for empty squares,
+    // x moves next when both have the same amount of squares, otherwise o
+    pub fn position_from_cells(x_size: usize, y_size: usize, seq_to_win: usize, cells: &str) -> Option<(TicTacToe, char, char)> {
+        if cells.chars().count() != x_size * y_size || !TicTacToe::fits_bitboard(x_size, y_size) {
+            return None
+        }
+        let xs = cells.chars().filter(|c| *c == 'x').count();
+        let os = cells.chars().filter(|c| *c == 'o').count();
+        let (symbol, op_symbol) = match xs.checked_sub(os) {
+            Some(0) => ('X', 'O'),
+            Some(1) => ('O', 'X'),
+            _ => return None
+        };
+        let mut board = TicTacToe::new(x_size, y_size, seq_to_win, ' ');
+        for (i, c) in cells.chars().enumerate() {
+            let state = match c {
+                'x' => SquareState::Filled('X'),
+                'o' => SquareState::Filled('O'),
+                '-' => continue,
+                _ => return None
+            };
+            board.set_square_from_index(i, state).ok()?; // a position with a line already made can't be proven
+        }
+        Some((board, symbol, op_symbol))
+    }
+
+    // whether symbol, which moves next, can force a win, with the main line of the proof as board indexes
+    pub fn prove(&mut self, board: &TicTacToe, symbol: char, op_symbol: char) -> (ProofResult, Vec<usize>) {
+        self.tree = vec![ProofNode { move_index: 0, parent: 0, first_child: 0, children: 0, proof: 1, disproof: 1 }];
+        self.nodes = 1;
+        self.solved.clear();
+        if board.is_finished() {
+            return (ProofResult::Disproven, vec![])
+        }
+        let start = Instant::now();
+        let mut board = board.clone();
+        while self.tree[0].proof != 0 && self.tree[0].disproof != 0 {
+            if self.tree.len() >= self.max_nodes || self.time_limit.is_some_and(|limit| start.elapsed() >= limit) {
+                break
+            }
+            let (leaf, depth) = self.most_proving_leaf(&mut board, symbol, op_symbol);
+            let (mut node, mut or_node) = (leaf, depth % 2 == 0);
+            self.expand(leaf, &mut board, or_node, symbol, op_symbol);
+            loop {
+                self.update(node, or_node);
+                if self.tree[node].proof == 0 || self.tree[node].disproof == 0 {
+                    self.solved.entry(board.position_hash()).or_insert(node);
+                }
+                if node == 0 {
+                    break
+                }
+                board.undo();
+                node = self.tree[node].parent;
+                or_node = !or_node;
+            }
+        }
+        self.nodes = self.tree.len();
+        let result = if self.tree[0].proof == 0 {
+            ProofResult::Proven
+        }else if self.tree[0].disproof == 0 {
+            ProofResult::Disproven
+        }else {
+            ProofResult::Unknown
+        };
+        (result, self.main_line(&board, symbol, op_symbol, result))
+    }
+
+    fn children(&self, node: usize) -> std::ops::Range<usize> {
+        self.tree[node].first_child..self.tree[node].first_child + self.tree[node].children
+    }
+
+    fn is_solved(&self, node: usize, result: ProofResult) -> bool {
+        match result {
+            ProofResult::Proven => self.tree[node].proof == 0,
+            ProofResult::Disproven => self.tree[node].disproof == 0,
+            ProofResult::Unknown => false
+        }
+    }
+
+    // plays the moves on the way, so the board ends up in the position of the leaf
+    fn most_proving_leaf(&self, board: &mut TicTacToe, symbol: char, op_symbol: char) -> (usize, usize) {
+        let (mut node, mut depth) = (0, 0);
+        while self.tree[node].children > 0 {
+            let or_node = depth % 2 == 0;
+            node = if or_node {
+                self.children(node).min_by_key(|child| self.tree[*child].proof).unwrap()
+            }else {
+                self.children(node).min_by_key(|child| self.tree[*child].disproof).unwrap()
+            };
+            let mover = if or_node { symbol } else { op_symbol };
+            board.set_square_from_index(self.tree[node].move_index, SquareState::Filled(mover)).unwrap();
+            depth += 1;
+        }
+        (node, depth)
+    }
+
+    fn expand(&mut self, node: usize, board: &mut TicTacToe, or_node: bool, symbol: char, op_symbol: char) {
+        let mover = if or_node { symbol } else { op_symbol };
+        let moves = if or_node && self.candidate_radius > 0 {
+            board.candidate_moves(self.candidate_radius)
+        }else {
+            (0..board.size()).filter(|i| board.squares[*i] == SquareState::None).collect()
+        };
+        // squares where the other side wins on its next move, any move that doesn't take one of them loses right away
+        let threats = ProofNumberSearch::winning_squares(board, if or_node { op_symbol } else { symbol });
+        let mut children = vec![];
+        for index in moves {
+            let (proof, disproof) = match board.set_square_from_index(index, SquareState::Filled(mover)) {
+                Ok(TurnState::Victory(_)) if or_node => (0, INFINITY),
+                Ok(TurnState::Victory(_)) => (INFINITY, 0),
+                Ok(TurnState::Draw) => (INFINITY, 0),
+                Ok(TurnState::Continue) => match self.solved.get(&board.position_hash()) { // reached before by other moves
+                    Some(solved) => (self.tree[*solved].proof, self.tree[*solved].disproof),
+                    None if threats.iter().any(|threat| *threat != index) => if or_node { (INFINITY, 0) } else { (0, INFINITY) },
+                    None if !ProofNumberSearch::can_still_win(board, symbol) => (INFINITY, 0),
+                    None => (1, 1)
+                },
+                Err(_) => continue
+            };
+            board.undo();
+            let child = ProofNode { move_index: index, parent: node, first_child: 0, children: 0, proof, disproof };
+            if proof == 0 && or_node || disproof == 0 && !or_node { // a move that settles the node is the only one worth keeping
+                children = vec![child];
+                break
+            }
+            children.push(child);
+        }
+        self.tree[node].first_child = self.tree.len();
+        self.tree[node].children = children.len();
+        self.tree.extend(children);
+    }
+
+    fn winning_squares(board: &mut TicTacToe, symbol: char) -> Vec<usize> {
+        let mut squares = vec![];
+        for i in 0..board.size() {
+            if board.squares[i] != SquareState::None {
+                continue
+            }
+            if let Ok(state) = board.set_square_from_index(i, SquareState::Filled(symbol)) {
+                if matches!(state, TurnState::Victory(_)) {
+                    squares.push(i)
+                }
+                board.undo();
+            }
+        }
+        squares
+    }
+
+    // false once every line has a square of the other player in it
+    fn can_still_win(board: &TicTacToe, symbol: char) -> bool {
+        let blocked = |x: usize, y: usize| matches!(board.get_square(x, y), Some(SquareState::Filled(c)) if *c != symbol);
+        let seq = board.seq_to_win as i32;
+        (0..board.y_size as i32).any(|y| (0..board.x_size as i32).any(|x| {
+            [(1, 0), (0, 1), (1, 1), (1, -1)].iter().any(|(step_x, step_y)| {
+                let (end_x, end_y) = (x + step_x * (seq - 1), y + step_y * (seq - 1));
+                end_x < board.x_size as i32 && end_y >= 0 && end_y < board.y_size as i32 &&
+                    (0..seq).all(|n| !blocked((x + step_x * n) as usize, (y + step_y * n) as usize))
+            })
+        }))
+    }
+
+    fn update(&mut self, node: usize, or_node: bool) {
+        if self.tree[node].children == 0 {
+            return
+        }
+        let proofs = self.children(node).map(|child| self.tree[child].proof);
+        let disproofs = self.children(node).map(|child| self.tree[child].disproof);
+        let (proof, disproof) = if or_node {
+            (proofs.min().unwrap(), disproofs.fold(0, u32::saturating_add))
+        }else {
+            (proofs.fold(0, u32::saturating_add), disproofs.min().unwrap())
+        };
+        self.tree[node].proof = proof;
+        self.tree[node].disproof = disproof;
+    }
+
+    // moves until the proof tree under the node ends, when the winner picks the fastest way and the loser the slowest
+    fn length(&self, node: usize, or_node: bool, result: ProofResult) -> usize {
+        let lengths = self.children(node).filter(|child| self.is_solved(*child, result)).map(|child| self.length(child, !or_node, result) + 1);
+        if or_node == (result == ProofResult::Proven) {
+            lengths.min().unwrap_or(0)
+        }else {
+            lengths.max().unwrap_or(0)
+        }
+    }
+
+    // for an unknown result it's the way to the leaf the search would have looked at next
+    fn main_line(&self, board: &TicTacToe, symbol: char, op_symbol: char, result: ProofResult) -> Vec<usize> {
+        let mut board = board.clone();
+        let (mut line, mut node, mut or_node) = (vec![], 0, true);
+        loop {
+            if self.tree[node].children == 0 {
+                // a position reached before by other moves keeps its proof under the node that got there first
+                match self.solved.get(&board.position_hash()) {
+                    Some(solved) if *solved != node && self.tree[*solved].children > 0 => node = *solved,
+                    _ => break
+                }
+            }
+            node = match result {
+                ProofResult::Unknown if or_node => self.children(node).min_by_key(|child| self.tree[*child].proof).unwrap(),
+                ProofResult::Unknown => self.children(node).min_by_key(|child| self.tree[*child].disproof).unwrap(),
+                _ => {
+                    let winner_to_move = or_node == (result == ProofResult::Proven);
+                    let solved = self.children(node).filter(|child| self.is_solved(*child, result));
+                    let lengths = solved.map(|child| (self.length(child, !or_node, result), child));
+                    let best = if winner_to_move { lengths.min_by_key(|(length, _)| *length) } else { lengths.max_by_key(|(length, _)| *length) };
+                    match best {
+                        Some((_, child)) => child,
+                        None => break
+                    }
+                }
+            };
+            let mover = if or_node { symbol } else { op_symbol };
+            board.set_square_from_index(self.tree[node].move_index, SquareState::Filled(mover)).unwrap();
+            line.push(self.tree[node].move_index);
+            or_node = !or_node;
+        }
+        // the line can end on a move that leaves a win the other side can't stop, that win ends it
+        let mover = if or_node { symbol } else { op_symbol };
+        if result != ProofResult::Unknown && !board.is_finished() {
+            line.extend(ProofNumberSearch::winning_squares(&mut board, mover).first());
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+    use crate::game::ai::solver::{GameValue, Solver};
+
+    #[test]
+    fn proofs_match_the_solver() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (mut proven, mut disproven) = (0, 0);
+        for (x_size, y_size, seq_to_win) in [(3, 3, 3), (4, 3, 3), (4, 4, 3)] {
+            let mut solver = Solver::new(x_size, y_size);
+            for _ in 0..10 {
+                let mut board = TicTacToe::new(x_size, y_size, seq_to_win, ' ');
+                let (mut symbol, mut op_symbol) = ('X', 'O');
+                for _ in 0..rng.gen_range(0..board.size() / 2) {
+                    let empty: Vec<usize> = (0..board.size()).filter(|i| board.squares[*i] == SquareState::None).collect();
+                    if board.set_square_from_index(*empty.choose(&mut rng).unwrap(), SquareState::Filled(symbol)).unwrap() != TurnState::Continue {
+                        break
+                    }
+                    (symbol, op_symbol) = (op_symbol, symbol);
+                }
+                if board.is_finished() {
+                    continue
+                }
+                let (result, line) = ProofNumberSearch::new(DEFAULT_MAX_NODES).prove(&board, symbol, op_symbol);
+                match solver.solve(&board, symbol, op_symbol).0 {
+                    GameValue::Win(_) => {
+                        assert_eq!(result, ProofResult::Proven, "{}", board);
+                        proven += 1;
+                        // the first move of the main line keeps the win
+                        if board.set_square_from_index(line[0], SquareState::Filled(symbol)).unwrap() == TurnState::Continue {
+                            assert!(matches!(solver.solve(&board, op_symbol, symbol).0, GameValue::Loss(_)), "{}", board);
+                        }
+                    }
+                    _ => {
+                        assert_eq!(result, ProofResult::Disproven, "{}", board);
+                        disproven += 1;
+                    }
+                }
+            }
+        }
+        assert!(proven > 0 && disproven > 0);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,78 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use crate::game::ai::solver::GameValue;
+
+#[derive(Copy, Clone, PartialEq, Default)]
+pub enum MoveSource {
+    #[default]
+    Search,
+    OpeningBook,
+    Tablebase,
+    ThreatSpace // a sequence of threats the opponent can't stop
+}
+
+#[derive(Clone, Default)]
+pub struct SearchStats {
+    pub depth_reached: usize, // deepest iteration that finished before the time ran out
+    pub max_depth: usize, // deepest position visited, counting the unfinished iteration
+    pub nodes: usize,
+    pub beta_cutoffs: usize,
+    pub search_time: Duration,
+    pub time_budget: Option<Duration>,
+    pub game_value: Option<GameValue>, // only known when the result is forced
+    pub root_score: Option<i32>,
+    pub principal_variation: Vec<(usize, usize)>, // expected moves from both players, starting with the chosen one
+    pub move_ordering_nodes: Option<(usize, usize)>, // nodes of the finished iterations with and without move ordering
+    pub source: MoveSource
+}
+
+impl SearchStats {
+    pub fn nodes_per_second(&self) -> f64 {
+        self.nodes as f64 / self.search_time.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.source {
+            MoveSource::Search => {}
+            MoveSource::OpeningBook => return write!(f, "Opening Book Move\nSearch Time: {}", self.search_time.as_secs_f64()),
+            MoveSource::Tablebase => {
+                write!(f, "Tablebase Move\nSearch Time: {}", self.search_time.as_secs_f64())?;
+                if let Some(value) = self.game_value {
+                    write!(f, "\nGame Value: {}", value)?;
+                }
+                return Ok(())
+            }
+            MoveSource::ThreatSpace => {
+                write!(f, "Threat Space Move\nSearch Time: {}\nNodes: {}\nThreat Sequence:", self.search_time.as_secs_f64(), self.nodes)?;
+                for (x, y) in &self.principal_variation {
+                    write!(f, " ({}, {})", x + 1, y + 1)?;
+                }
+                return Ok(())
+            }
+        }
+        write!(f, "Search Depth: {} (max: {})\nSearch Time: {}", self.depth_reached, self.max_depth, self.search_time.as_secs_f64())?;
+        if let Some(budget) = self.time_budget {
+            write!(f, " (budget: {})", budget.as_secs_f64())?;
+        }
+        write!(f, "\nNodes: {} ({:.0} per second)\nBeta Cutoffs: {}", self.nodes, self.nodes_per_second(), self.beta_cutoffs)?;
+        if let Some((ordered, unordered)) = self.move_ordering_nodes {
+            let reduction = 100.0 * (1.0 - ordered as f64 / unordered.max(1) as f64);
+            write!(f, "\nMove Ordering: {} nodes instead of {} ({:.1}% fewer)", ordered, unordered, reduction)?;
+        }
+        if let Some(score) = self.root_score {
+            write!(f, "\nRoot Score: {}", score)?;
+        }
+        if let Some(value) = self.game_value {
+            write!(f, "\nGame Value: {}", value)?;
+        }
+        if !self.principal_variation.is_empty() {
+            write!(f, "\nPrincipal Variation:")?;
+            for (x, y) in &self.principal_variation {
+                write!(f, " ({}, {})", x + 1, y + 1)?;
+            }
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use crate::game::ai::transposition_table::Bound;
+use crate::game::symmetry::BoardSymmetries;
+use crate::game::tictactoe_core::{SquareState, TicTacToe, TurnState};
+
+pub const MAX_SQUARES: usize = 16; // 4x4 and smaller boards can be solved in a reasonable time
+const WIN_SCORE: i32 = 1000;
+const INFINITY: i32 = WIN_SCORE + 1;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameValue {
+    Win(usize), // amount of moves until the game ends, counting both players
+    Draw,
+    Loss(usize)
+}
+
+impl GameValue {
+    fn from_score(score: i32) -> GameValue {
+        if score > 0 {
+            GameValue::Win((WIN_SCORE - score) as usize)
+        }else if score < 0 {
+            GameValue::Loss((WIN_SCORE + score) as usize)
+        }else {
+            GameValue::Draw
+        }
+    }
+}
+
+impl Display for GameValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameValue::Win(n) => write!(f, "Win in {}", n),
+            GameValue::Draw => write!(f, "Draw"),
+            GameValue::Loss(n) => write!(f, "Loss in {}", n),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct SolverEntry {
+    score: i32,
+    bound: Bound,
+    best_move: usize // in the coordinates of the canonical board
+}
+
+pub struct Solver {
+    symmetries: BoardSymmetries,
+    table: HashMap<u64, SolverEntry>, // keyed by the canonical hash, so every symmetric board shares its entry
+    pub nodes: usize
+}
+
+// scores are relative to the node they belong to: WIN_SCORE - n is a win in n moves, -(WIN_SCORE - n) a loss in n moves
+fn to_parent_score(score: i32) -> i32 {
+    if score > 0 {
+        -score + 1
+    }else if score < 0 {
+        -score - 1
+    }else {
+        0
+    }
+}
+
+fn to_child_score(score: i32) -> i32 { // inverse of to_parent_score, used to pass the alpha-beta window down
+    if score > 0 {
+        -score - 1
+    }else if score < 0 {
+        -score + 1
+    }else {
+        0
+    }
+}
+
+impl Solver {
+    pub fn new(x_size: usize, y_size: usize) -> Solver {
+        Solver {
+            symmetries: BoardSymmetries::new(x_size, y_size),
+            table: HashMap::new(),
+            nodes: 0
+        }
+    }
+
+    pub fn can_solve(board: &TicTacToe) -> bool {
+        board.size() <= MAX_SQUARES
+    }
+
+    pub fn solve(&mut self, board: &TicTacToe, symbol: char, op_symbol: char) -> (GameValue, Option<usize>) {
+        if board.winning_line.is_some() {
+            return (GameValue::Loss(0), None)
+        }
+        if board.is_finished() {
+            return (GameValue::Draw, None)
+        }
+        let mut board = board.clone();
+        let (score, best_move) = self.negamax(&mut board, symbol, op_symbol, -INFINITY, INFINITY);
+        (GameValue::from_score(score), Some(best_move))
+    }
+
+    fn negamax(&mut self, board: &mut TicTacToe, symbol: char, op_symbol: char, mut alpha: i32, mut beta: i32) -> (i32, usize) {
+        self.nodes += 1;
+        let hashes = self.symmetries.hashes(board);
+        let (mut key, mut symmetry) = (hashes[0], 0);
+        for (s, hash) in hashes.iter().enumerate() {
+            if *hash < key {
+                (key, symmetry) = (*hash, s)
+            }
+        }
+        let original_alpha = alpha;
+        let mut table_move = None;
+        if let Some(entry) = self.table.get(&key) {
+            let best_move = self.symmetries.unmap_index(symmetry, entry.best_move);
+            match entry.bound {
+                Bound::Exact => return (entry.score, best_move),
+                Bound::Lower => alpha = alpha.max(entry.score),
+                Bound::Upper => beta = beta.min(entry.score)
+            }
+            if alpha >= beta {
+                return (entry.score, best_move)
+            }
+            table_move = Some(best_move);
+        }
+        // moves that are mirrors of each other on a symmetric board lead to the same position, only the first is searched
+        let fixed_symmetries: Vec<usize> = (1..hashes.len()).filter(|s| hashes[*s] == hashes[0]).collect();
+        let mut moves: Vec<usize> = (0..board.size()).filter(|i| {
+            board.get_square_by_index(*i) == Some(&SquareState::None) &&
+                fixed_symmetries.iter().all(|s| self.symmetries.map_index(*s, *i) >= *i)
+        }).collect();
+        if let Some(i) = moves.iter().position(|m| Some(*m) == table_move) {
+            moves[..=i].rotate_right(1);
+        }
+        let mut best = (-INFINITY, moves[0]);
+        for m in moves {
+            let score = match board.set_square_from_index(m, SquareState::Filled(symbol)) {
+                Ok(TurnState::Victory(_)) => WIN_SCORE - 1,
+                Ok(TurnState::Draw) => 0,
+                Ok(TurnState::Continue) => {
+                    let (child_score, _) = self.negamax(board, op_symbol, symbol, to_child_score(beta), to_child_score(alpha));
+                    to_parent_score(child_score)
+                }
+                Err(_) => continue
+            };
+            board.undo();
+            if score > best.0 {
+                best = (score, m);
+            }
+            if score == WIN_SCORE - 1 { // nothing beats winning right now
+                break
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break
+            }
+        }
+        let bound = if best.0 <= original_alpha {
+            Bound::Upper
+        }else if best.0 >= beta {
+            Bound::Lower
+        }else {
+            Bound::Exact
+        };
+        self.table.insert(key, SolverEntry { score: best.0, bound, best_move: self.symmetries.map_index(symmetry, best.1) });
+        best
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    // plain negamax without the table, symmetries or alpha-beta, in the same scores
+    fn minimax(board: &mut TicTacToe, symbol: char, op_symbol: char) -> i32 {
+        let mut best = -INFINITY;
+        for i in 0..board.size() {
+            if board.squares[i] != SquareState::None {
+                continue
+            }
+            let score = match board.set_square_from_index(i, SquareState::Filled(symbol)).unwrap() {
+                TurnState::Victory(_) => WIN_SCORE - 1,
+                TurnState::Draw => 0,
+                TurnState::Continue => to_parent_score(minimax(board, op_symbol, symbol))
+            };
+            board.undo();
+            best = best.max(score);
+        }
+        best
+    }
+
+    #[test]
+    fn empty_boards_have_their_known_values() {
+        assert_eq!(Solver::new(3, 3).solve(&TicTacToe::new(3, 3, 3, ' '), 'X', 'O').0, GameValue::Draw);
+        assert!(matches!(Solver::new(4, 4).solve(&TicTacToe::new(4, 4, 3, ' '), 'X', 'O').0, GameValue::Win(_)));
+    }
+
+    #[test]
+    fn solved_values_match_a_plain_search() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (x_size, y_size, seq_to_win, first_moves) in [(3, 3, 3, 2), (4, 3, 3, 4), (4, 4, 3, 8)] {
+            let mut solver = Solver::new(x_size, y_size);
+            for _ in 0..20 {
+                let mut board = TicTacToe::new(x_size, y_size, seq_to_win, ' ');
+                let (mut symbol, mut op_symbol) = ('X', 'O');
+                for _ in 0..first_moves { // so the plain search stays fast
+                    if board.is_finished() {
+                        break
+                    }
+                    let empty: Vec<usize> = (0..board.size()).filter(|i| board.squares[*i] == SquareState::None).collect();
+                    board.set_square_from_index(*empty.choose(&mut rng).unwrap(), SquareState::Filled(symbol)).unwrap();
+                    (symbol, op_symbol) = (op_symbol, symbol);
+                }
+                if board.is_finished() {
+                    continue
+                }
+                let (value, best_move) = solver.solve(&board, symbol, op_symbol);
+                assert_eq!(value, GameValue::from_score(minimax(&mut board, symbol, op_symbol)), "{}", board);
+                // the best move keeps the value
+                let after = match board.set_square_from_index(best_move.unwrap(), SquareState::Filled(symbol)).unwrap() {
+                    TurnState::Continue => GameValue::from_score(to_parent_score(minimax(&mut board, op_symbol, symbol))),
+                    TurnState::Victory(_) => GameValue::Win(1),
+                    TurnState::Draw => GameValue::Draw
+                };
+                assert_eq!(value, after, "{}", board);
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,357 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use crate::game::ai::solver::{GameValue, MAX_SQUARES};
+use crate::game::symmetry::BoardSymmetries;
+use crate::game::tictactoe_core::{SquareState, TicTacToe};
+
+const MAGIC: &[u8; 4] = b"TTTB";
+const VERSION: u8 = 1;
+// a value byte keeps the result in the high bits and the amount of moves until the game ends in the low ones
+const DRAW: u8 = 0;
+const WIN: u8 = 0x40;
+const LOSS: u8 = 0x80;
+const DISTANCE_MASK: u8 = 0x3f;
+
+// squares of a position as two bit masks, from the point of view of the side to move
+#[derive(Copy, Clone)]
+struct Position {
+    mover: u16,
+    opponent: u16
+}
+
+impl Position {
+    fn key(&self) -> u32 {
+        (self.mover as u32) << 16 | self.opponent as u32
+    }
+    fn from_key(key: u32) -> Position {
+        Position { mover: (key >> 16) as u16, opponent: key as u16 }
+    }
+}
+
+struct Geometry {
+    full: u16,
+    lines_through: Vec<Vec<u16>>, // every winning line that goes through a square
+    symmetry_bytes: Vec<[[u16; 256]; 2]> // where the bits of each byte go under a symmetry
+}
+
+impl Geometry {
+    fn new(x_size: usize, y_size: usize, seq_to_win: usize) -> Geometry {
+        let size = x_size * y_size;
+        let mut lines_through = vec![vec![]; size];
+        for y in 0..y_size as i32 {
+            for x in 0..x_size as i32 {
+                for (step_x, step_y) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+                    let squares: Vec<(i32, i32)> = (0..seq_to_win as i32).map(|n| (x + step_x * n, y + step_y * n)).collect();
+                    if squares.iter().all(|(x, y)| *x >= 0 && *y >= 0 && *x < x_size as i32 && *y < y_size as i32) {
+                        let line = squares.iter().fold(0u16, |line, (x, y)| line | 1 << (x + y * x_size as i32));
+                        for (x, y) in squares {
+                            lines_through[(x + y * x_size as i32) as usize].push(line)
+                        }
+                    }
+                }
+            }
+        }
+        let symmetries = BoardSymmetries::new(x_size, y_size);
+        let symmetry_bytes = (0..symmetries.len()).map(|symmetry| {
+            let mut bytes = [[0u16; 256]; 2];
+            for (half, table) in bytes.iter_mut().enumerate() {
+                for (byte, mapped) in table.iter_mut().enumerate() {
+                    for bit in 0..8 {
+                        let square = half * 8 + bit;
+                        if byte & 1 << bit != 0 && square < size {
+                            *mapped |= 1 << symmetries.map_index(symmetry, square)
+                        }
+                    }
+                }
+            }
+            bytes
+        }).collect();
+        Geometry { full: ((1u32 << size) - 1) as u16, lines_through, symmetry_bytes }
+    }
+
+    fn wins(&self, squares: u16, placed: usize) -> bool {
+        self.lines_through[placed].iter().any(|line| squares & line == *line)
+    }
+
+    fn canonical(&self, position: Position) -> u32 { // smallest key among every symmetry of the position
+        self.symmetry_bytes.iter().map(|bytes| {
+            let map = |mask: u16| bytes[0][(mask & 0xff) as usize] | bytes[1][(mask >> 8) as usize];
+            Position { mover: map(position.mover), opponent: map(position.opponent) }.key()
+        }).min().unwrap()
+    }
+}
+
+pub struct Tablebase {
+    pub x_size: usize,
+    pub y_size: usize,
+    pub seq_to_win: usize,
+    keys: Vec<u32>, // sorted canonical keys, so probing is a binary search
+    values: Vec<u8>,
+    geometry: Geometry
+}
+
+#[derive(Debug)]
+pub enum TablebaseError {
+    Io(std::io::Error),
+    InvalidFile
+}
+
+fn encode(value: GameValue) -> u8 {
+    match value {
+        GameValue::Win(n) => WIN | n as u8,
+        GameValue::Draw => DRAW,
+        GameValue::Loss(n) => LOSS | n as u8
+    }
+}
+
+fn decode(value: u8) -> GameValue {
+    match value & !DISTANCE_MASK {
+        WIN => GameValue::Win((value & DISTANCE_MASK) as usize),
+        LOSS => GameValue::Loss((value & DISTANCE_MASK) as usize),
+        _ => GameValue::Draw
+    }
+}
+
+fn rank(value: GameValue) -> i32 { // higher is better for the side to move, faster wins and slower losses first
+    match value {
+        GameValue::Win(n) => 100 - n as i32,
+        GameValue::Draw => 0,
+        GameValue::Loss(n) => n as i32 - 100
+    }
+}
+
+fn parent_value(value: GameValue) -> GameValue { // the value of the move that leads to a position, for the player who made it
+    match value {
+        GameValue::Win(n) => GameValue::Loss(n + 1),
+        GameValue::Draw => GameValue::Draw,
+        GameValue::Loss(n) => GameValue::Win(n + 1)
+    }
+}
+
+impl Tablebase {
+    pub fn fits_board(x_size: usize, y_size: usize) -> bool {
+        x_size * y_size <= MAX_SQUARES
+    }
+
+    pub fn fits(&self, board: &TicTacToe) -> bool {
+        board.x_size == self.x_size && board.y_size == self.y_size && board.seq_to_win == self.seq_to_win
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn value(&self, key: u32) -> Option<GameValue> {
+        self.keys.binary_search(&key).ok().map(|i| decode(self.values[i]))
+    }
+
+    // value of every move on the board, for the symbol to move, None if a position after one of them is missing
+    fn move_values(&self, position: Position) -> Option<Vec<(usize, GameValue)>> {
+        let empty = self.geometry.full & !(position.mover | position.opponent);
+        (0..self.x_size * self.y_size).filter(|square| empty & 1 << square != 0).map(|square| {
+            let mover = position.mover | 1 << square;
+            if self.geometry.wins(mover, square) {
+                Some((square, GameValue::Win(1)))
+            }else if mover | position.opponent == self.geometry.full {
+                Some((square, GameValue::Draw))
+            }else {
+                let child = Position { mover: position.opponent, opponent: mover };
+                Some((square, parent_value(self.value(self.geometry.canonical(child))?)))
+            }
+        }).collect()
+    }
+
+    fn best_value(moves: &[(usize, GameValue)]) -> u8 {
+        moves.iter().map(|(_, value)| *value).max_by_key(|value| rank(*value)).map_or(DRAW, encode)
+    }
+
+    // every position has to be there with the value its moves give it, so a file made for another board or cut short is never used
+    fn is_complete(&self) -> bool {
+        let legal = |key: &u32| {
+            let position = Position::from_key(*key);
+            position.mover & position.opponent == 0 && (position.mover | position.opponent) & !self.geometry.full == 0
+        };
+        self.keys.windows(2).all(|pair| pair[0] < pair[1]) && self.keys.iter().all(legal) && self.value(0).is_some() &&
+            self.keys.iter().zip(self.values.iter()).all(|(key, value)| {
+                self.move_values(Position::from_key(*key)).is_some_and(|moves| Tablebase::best_value(&moves) == *value)
+            })
+    }
+
+    fn position(board: &TicTacToe, symbol: char) -> Position {
+        let mut position = Position { mover: 0, opponent: 0 };
+        for (i, square) in board.squares.iter().enumerate() {
+            match square {
+                SquareState::Filled(c) if *c == symbol => position.mover |= 1 << i,
+                SquareState::Filled(_) => position.opponent |= 1 << i,
+                SquareState::None => {}
+            }
+        }
+        position
+    }
+
+    pub fn empty_board_value(&self) -> Option<GameValue> { // for the first player
+        self.value(0)
+    }
+
+    pub fn best_move(&self, board: &TicTacToe, symbol: char, rng: &mut impl Rng) -> Option<(usize, GameValue)> { // a random one among the equally good
+        if !self.fits(board) || board.is_finished() {
+            return None
+        }
+        let moves = self.move_values(Tablebase::position(board, symbol))?; // None for a position the game can't reach, like a wrong side to move
+        let best = moves.iter().map(|(_, value)| rank(*value)).max()?;
+        let best_moves: Vec<&(usize, GameValue)> = moves.iter().filter(|(_, value)| rank(*value) == best).collect();
+        best_moves.choose(rng).map(|m| **m)
+    }
+
+    // every reachable position gets listed one move at a time, then their values get worked out from the last move back to the first
+    pub fn generate(x_size: usize, y_size: usize, seq_to_win: usize) -> Option<Tablebase> {
+        if !Tablebase::fits_board(x_size, y_size) {
+            return None
+        }
+        let size = x_size * y_size;
+        let mut tablebase = Tablebase { x_size, y_size, seq_to_win, keys: vec![], values: vec![], geometry: Geometry::new(x_size, y_size, seq_to_win) };
+        let mut levels: Vec<Vec<u32>> = vec![vec![0]];
+        for stones in 0..size {
+            let mut next_level = HashSet::new();
+            for key in &levels[stones] {
+                let position = Position::from_key(*key);
+                let empty = tablebase.geometry.full & !(position.mover | position.opponent);
+                for square in (0..size).filter(|square| empty & 1 << square != 0) {
+                    let mover = position.mover | 1 << square;
+                    if !tablebase.geometry.wins(mover, square) && stones + 1 < size {
+                        next_level.insert(tablebase.geometry.canonical(Position { mover: position.opponent, opponent: mover }));
+                    }
+                }
+            }
+            let mut next_level: Vec<u32> = next_level.into_iter().collect();
+            next_level.sort_unstable();
+            println!("{} moves: {} positions", stones + 1, next_level.len());
+            levels.push(next_level);
+        }
+        for level in levels.into_iter().rev() {
+            // the values of the next level are already in the table, and the ones of this level are only needed by the previous one
+            let values: Vec<u8> = level.iter().map(|key| {
+                let moves = tablebase.move_values(Position::from_key(*key)).unwrap_or_else(|| unreachable!("the next level is already in the table"));
+                Tablebase::best_value(&moves)
+            }).collect();
+            let mut entries: Vec<(u32, u8)> = tablebase.keys.iter().copied().zip(tablebase.values.iter().copied()).collect();
+            entries.extend(level.into_iter().zip(values));
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            (tablebase.keys, tablebase.values) = entries.into_iter().unzip();
+        }
+        Some(tablebase)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TablebaseError> {
+        let mut bytes = Vec::with_capacity(12 + self.keys.len() * 5);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&[VERSION, self.x_size as u8, self.y_size as u8, self.seq_to_win as u8]);
+        bytes.extend_from_slice(&(self.keys.len() as u32).to_le_bytes());
+        for key in &self.keys {
+            bytes.extend_from_slice(&key.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.values);
+        std::fs::write(path, bytes).map_err(TablebaseError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Tablebase, TablebaseError> {
+        let bytes = std::fs::read(path).map_err(TablebaseError::Io)?;
+        if bytes.len() < 12 || &bytes[0..4] != MAGIC || bytes[4] != VERSION {
+            return Err(TablebaseError::InvalidFile)
+        }
+        let (x_size, y_size, seq_to_win) = (bytes[5] as usize, bytes[6] as usize, bytes[7] as usize);
+        let count = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        if x_size == 0 || y_size == 0 || seq_to_win == 0 || seq_to_win > x_size.max(y_size) || !Tablebase::fits_board(x_size, y_size) || bytes.len() != 12 + count * 5 {
+            return Err(TablebaseError::InvalidFile)
+        }
+        let keys = bytes[12..12 + count * 4].chunks_exact(4).map(|key| u32::from_le_bytes(key.try_into().unwrap())).collect();
+        let values = bytes[12 + count * 4..].to_vec();
+        let tablebase = Tablebase { x_size, y_size, seq_to_win, keys, values, geometry: Geometry::new(x_size, y_size, seq_to_win) };
+        if !tablebase.is_complete() {
+            return Err(TablebaseError::InvalidFile)
+        }
+        Ok(tablebase)
+    }
+}
+
+impl Display for TablebaseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TablebaseError::Io(e) => write!(f, "Couldn't access the tablebase file: {}", e),
+            TablebaseError::InvalidFile => write!(f, "That file isn't a tablebase, or it's missing positions!"),
+        }
+    }
+}
+
+impl std::error::Error for TablebaseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::game::ai::solver::Solver;
+    use crate::game::tictactoe_core::TurnState;
+
+    // every position reachable from the empty board, with the symbol to move
+    fn positions(board: &mut TicTacToe, symbol: char, op_symbol: char, found: &mut Vec<(TicTacToe, char)>) {
+        found.push((board.clone(), symbol));
+        for i in 0..board.size() {
+            if board.squares[i] == SquareState::None {
+                if let Ok(TurnState::Continue) = board.set_square_from_index(i, SquareState::Filled(symbol)) {
+                    positions(board, op_symbol, symbol, found);
+                }
+                board.undo();
+            }
+        }
+    }
+
+    #[test]
+    fn best_moves_match_the_solver_on_3x3() {
+        let tablebase = Tablebase::generate(3, 3, 3).unwrap();
+        assert_eq!(tablebase.empty_board_value(), Some(GameValue::Draw));
+        let mut found = vec![];
+        positions(&mut TicTacToe::new(3, 3, 3, ' '), 'X', 'O', &mut found);
+        let mut solver = Solver::new(3, 3);
+        let mut rng = StdRng::seed_from_u64(0);
+        for (board, symbol) in found.iter().step_by(7) { // a spread of them, all of them take a while without optimizations
+            let op_symbol = if *symbol == 'X' { 'O' } else { 'X' };
+            let (_, value) = tablebase.best_move(board, *symbol, &mut rng).unwrap();
+            assert_eq!(value, solver.solve(board, *symbol, op_symbol).0, "{}", board);
+        }
+    }
+
+    #[test]
+    fn load_rejects_a_tablebase_missing_a_position() {
+        let path = std::env::temp_dir().join(format!("tablebase_test_{}.bin", std::process::id()));
+        let tablebase = Tablebase::generate(3, 3, 3).unwrap();
+        tablebase.save(&path).unwrap();
+        assert!(Tablebase::load(&path).is_ok());
+        let mut bytes = std::fs::read(&path).unwrap();
+        let count = tablebase.len();
+        bytes.drain(12 + (count - 1) * 4..12 + count * 4); // the last key and its value
+        bytes.pop();
+        bytes[8..12].copy_from_slice(&(count as u32 - 1).to_le_bytes());
+        std::fs::write(&path, bytes).unwrap();
+        let loaded = Tablebase::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(TablebaseError::InvalidFile)));
+    }
+
+    #[test]
+    fn unreachable_positions_have_no_best_move() {
+        let tablebase = Tablebase::generate(3, 3, 3).unwrap();
+        let mut board = TicTacToe::new(3, 3, 3, ' ');
+        board.set_square(1, 1, SquareState::Filled('X')).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(tablebase.best_move(&board, 'O', &mut rng).is_some());
+        assert_eq!(tablebase.best_move(&board, 'X', &mut rng), None); // X would have two stones more than O
+    }
+}
\ No newline at end of file
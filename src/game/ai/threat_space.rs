@@ -0,0 +1,321 @@
+use crate::game::tictactoe_core::{SquareState, TicTacToe, TurnState};
+
+pub const DEFAULT_THREAT_DEPTH: usize = 3;
+const MAX_NODES: usize = 3000; // per search, so looking for threats never takes longer than the search itself
+const FOURS_PER_THREE: usize = 3; // fours only have one reply, so they can be followed much deeper than threes
+
+// named after 5 in a row, a four is one move from winning and a three is one move from an open four, for any sequence to win
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Threat {
+    Five, // the move wins
+    OpenFour, // leaves two winning squares or more, only one of them can be blocked
+    Four, // leaves one winning square, that has to be blocked right away
+    OpenThree, // leaves a move that makes an open four, with the stones next to each other
+    BrokenThree // the same, with a gap between the stones
+}
+
+/*
+THE SEARCH ONLY PLAYS THREATS:
+    - A four has a single reply, the winning square it leaves
+    - A three can be stopped by taking the open four square or one of the squares it would win on,
+      or delayed by a four of the other side, that has to be blocked before going on
+    - Every reply has to lose for the threat to count, and the sequence ends when a threat can't be stopped
+Sequences where the other side can win first or answer a four with a four of its own are left out, so a sequence that
+is found is always a forced win, but not every forced win gets found.
+*/
+pub struct ThreatSpaceSearch {
+    pub max_depth: usize, // most threes in a sequence
+    pub nodes: usize,
+    seq_to_win: usize,
+    windows: Vec<Vec<usize>>, // every line of seq_to_win squares on the board, in order
+    windows_through: Vec<Vec<usize>> // the windows every square is part of
+}
+
+impl ThreatSpaceSearch {
+    pub fn new(board: &TicTacToe, max_depth: usize) -> ThreatSpaceSearch {
+        let (x_size, y_size, seq) = (board.x_size as i32, board.y_size as i32, board.seq_to_win as i32);
+        let mut windows = vec![];
+        let mut windows_through = vec![vec![]; board.size()];
+        for y in 0..y_size {
+            for x in 0..x_size {
+                for (step_x, step_y) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+                    let (end_x, end_y) = (x + step_x * (seq - 1), y + step_y * (seq - 1));
+                    if end_x >= x_size || end_y < 0 || end_y >= y_size {
+                        continue
+                    }
+                    let window: Vec<usize> = (0..seq).map(|n| (x + step_x * n + (y + step_y * n) * x_size) as usize).collect();
+                    for square in window.iter() {
+                        windows_through[*square].push(windows.len());
+                    }
+                    windows.push(window);
+                }
+            }
+        }
+        ThreatSpaceSearch { max_depth, nodes: 0, seq_to_win: board.seq_to_win, windows, windows_through }
+    }
+
+    pub fn fits(&self, board: &TicTacToe) -> bool {
+        board.size() == self.windows_through.len() && board.seq_to_win == self.seq_to_win
+    }
+
+    fn stones(&self, board: &TicTacToe, window: usize, symbol: char) -> Option<usize> { // None once the other side has a square in it
+        let mut stones = 0;
+        for square in self.windows[window].iter() {
+            match board.squares[*square] {
+                SquareState::Filled(c) if c == symbol => stones += 1,
+                SquareState::Filled(_) => return None,
+                SquareState::None => {}
+            }
+        }
+        Some(stones)
+    }
+
+    // empty squares of the windows that have exactly the given amount of symbols and none of the other side
+    fn empty_squares(&self, board: &TicTacToe, windows: impl Iterator<Item = usize>, symbol: char, stones: usize) -> Vec<usize> {
+        let mut squares: Vec<usize> = windows.filter(|window| self.stones(board, *window, symbol) == Some(stones))
+            .flat_map(|window| self.windows[window].iter().copied().filter(|square| board.squares[*square] == SquareState::None))
+            .collect();
+        squares.sort_unstable();
+        squares.dedup();
+        squares
+    }
+
+    pub fn winning_squares(&self, board: &TicTacToe, symbol: char) -> Vec<usize> {
+        self.empty_squares(board, 0..self.windows.len(), symbol, self.seq_to_win - 1)
+    }
+
+    fn winning_squares_through(&self, board: &TicTacToe, square: usize, symbol: char) -> Vec<usize> {
+        self.empty_squares(board, self.windows_through[square].iter().copied(), symbol, self.seq_to_win - 1)
+    }
+
+    pub fn four_moves(&self, board: &TicTacToe, symbol: char) -> Vec<usize> { // moves that leave at least one winning square
+        match self.seq_to_win.checked_sub(2) {
+            Some(stones) => self.empty_squares(board, 0..self.windows.len(), symbol, stones),
+            None => vec![]
+        }
+    }
+
+    // moves that leave two winning squares or more, in the given windows
+    fn open_four_moves(&self, board: &mut TicTacToe, windows: impl Iterator<Item = usize>, symbol: char) -> Vec<usize> {
+        let Some(stones) = self.seq_to_win.checked_sub(2) else {
+            return vec![]
+        };
+        let mut moves = vec![];
+        for square in self.empty_squares(board, windows, symbol, stones) {
+            if let Ok(state) = board.set_square_from_index(square, SquareState::Filled(symbol)) {
+                if state != TurnState::Continue || self.winning_squares_through(board, square, symbol).len() >= 2 {
+                    moves.push(square)
+                }
+                board.undo();
+            }
+        }
+        moves
+    }
+
+    pub fn threat(&self, board: &mut TicTacToe, square: usize, symbol: char) -> Option<Threat> {
+        let state = board.set_square_from_index(square, SquareState::Filled(symbol)).ok()?;
+        let threat = match state {
+            TurnState::Victory(_) => Some(Threat::Five),
+            TurnState::Draw => None,
+            TurnState::Continue => match self.winning_squares_through(board, square, symbol).len() {
+                0 => self.three(board, square, symbol),
+                1 => Some(Threat::Four),
+                _ => Some(Threat::OpenFour)
+            }
+        };
+        board.undo();
+        threat
+    }
+
+    fn three(&self, board: &mut TicTacToe, square: usize, symbol: char) -> Option<Threat> { // with the move already on the board
+        let stones = self.seq_to_win.checked_sub(2)?;
+        let windows: Vec<usize> = self.windows_through[square].iter().copied().filter(|window| self.stones(board, *window, symbol) == Some(stones)).collect();
+        let open_fours = self.open_four_moves(board, windows.iter().copied(), symbol);
+        let window = windows.iter().find(|window| self.windows[**window].iter().any(|square| open_fours.contains(square)))?;
+        let filled: Vec<usize> = (0..self.seq_to_win).filter(|n| board.squares[self.windows[*window][*n]] == SquareState::Filled(symbol)).collect();
+        if filled.last().zip(filled.first()).is_some_and(|(last, first)| last - first + 1 == filled.len()) {
+            Some(Threat::OpenThree)
+        }else {
+            Some(Threat::BrokenThree)
+        }
+    }
+
+    // every threat the symbol can make, the strongest first
+    pub fn threats(&self, board: &mut TicTacToe, symbol: char) -> Vec<(usize, Threat)> {
+        let least_stones = self.seq_to_win.saturating_sub(3);
+        let mut candidates: Vec<usize> = (least_stones..self.seq_to_win).flat_map(|stones| self.empty_squares(board, 0..self.windows.len(), symbol, stones)).collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        let mut threats: Vec<(usize, Threat)> = candidates.into_iter().filter_map(|square| Some((square, self.threat(board, square, symbol)?))).collect();
+        threats.sort_by_key(|(_, threat)| *threat);
+        threats
+    }
+
+    // a sequence of threats that wins for symbol, moving next, as the moves of both sides until the last threat
+    pub fn find_win(&mut self, board: &TicTacToe, symbol: char, op_symbol: char) -> Option<Vec<usize>> {
+        self.nodes = 0;
+        if board.is_finished() || !self.fits(board) {
+            return None
+        }
+        let mut board = board.clone();
+        // the shortest sequence of fours first, since they are cheap to follow, then with threes too
+        for depth in 1..=self.max_depth * FOURS_PER_THREE {
+            if let Some(line) = self.attack(&mut board, symbol, op_symbol, depth, true) {
+                return Some(line)
+            }
+        }
+        for depth in 1..=self.max_depth {
+            if let Some(line) = self.attack(&mut board, symbol, op_symbol, depth, false) {
+                return Some(line)
+            }
+        }
+        None
+    }
+
+    // the moves of symbol that stop every threat sequence op_symbol could start next, None when there is none to stop
+    pub fn defenses(&mut self, board: &TicTacToe, symbol: char, op_symbol: char) -> Option<Vec<usize>> {
+        let line = self.find_win(board, op_symbol, symbol)?;
+        let mut board = board.clone();
+        let least_stones = self.seq_to_win.saturating_sub(3);
+        let mut candidates = line.clone();
+        for stones in least_stones..self.seq_to_win { // the squares around the first threat
+            candidates.extend(self.empty_squares(&board, self.windows_through[line[0]].iter().copied(), op_symbol, stones));
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        let mut defenses = vec![];
+        for square in candidates {
+            let stops = match board.set_square_from_index(square, SquareState::Filled(symbol)) {
+                Ok(TurnState::Continue) => {
+                    let stops = self.find_win(&board, op_symbol, symbol).is_none();
+                    board.undo();
+                    stops
+                }
+                Ok(_) => {
+                    board.undo();
+                    true
+                }
+                Err(_) => false
+            };
+            if stops {
+                defenses.push(square)
+            }
+        }
+        (!defenses.is_empty()).then_some(defenses)
+    }
+
+    fn attack(&mut self, board: &mut TicTacToe, attacker: char, defender: char, depth: usize, only_fours: bool) -> Option<Vec<usize>> {
+        self.nodes += 1;
+        if let Some(square) = self.winning_squares(board, attacker).first() {
+            return Some(vec![*square])
+        }
+        if depth == 0 || self.nodes >= MAX_NODES || !self.winning_squares(board, defender).is_empty() {
+            return None
+        }
+        for (square, threat) in self.threats(board, attacker) {
+            if only_fours && threat > Threat::Four {
+                break
+            }
+            board.set_square_from_index(square, SquareState::Filled(attacker)).ok()?;
+            let line = self.defend(board, attacker, defender, depth - 1, only_fours);
+            board.undo();
+            if let Some(line) = line {
+                return Some([vec![square], line].concat())
+            }
+        }
+        None
+    }
+
+    // the defender moves after a threat, the line is there when every reply loses
+    fn defend(&mut self, board: &mut TicTacToe, attacker: char, defender: char, depth: usize, only_fours: bool) -> Option<Vec<usize>> {
+        self.nodes += 1;
+        if !self.winning_squares(board, defender).is_empty() {
+            return None
+        }
+        let wins = self.winning_squares(board, attacker);
+        let replies = match wins.len() {
+            0 if only_fours => return None,
+            0 => {
+                // every open four move has to be stopped, by taking it or a square it would win on
+                let open_fours = self.open_four_moves(board, 0..self.windows.len(), attacker);
+                if open_fours.is_empty() {
+                    return None
+                }
+                let stones = self.seq_to_win - 2;
+                let mut defenses = open_fours.iter().map(|square| {
+                    let mut squares = self.empty_squares(board, self.windows_through[*square].iter().copied(), attacker, stones);
+                    squares.push(*square);
+                    squares
+                }).reduce(|a, b| a.into_iter().filter(|square| b.contains(square)).collect()).unwrap();
+                defenses.extend(self.four_moves(board, defender));
+                defenses.sort_unstable();
+                defenses.dedup();
+                defenses
+            }
+            1 => wins,
+            _ => return Some(wins) // only one of them can be blocked
+        };
+        let mut main_line = None;
+        for reply in replies {
+            board.set_square_from_index(reply, SquareState::Filled(defender)).ok()?;
+            let line = match self.winning_squares(board, defender)[..] {
+                [] => self.attack(board, attacker, defender, depth, only_fours),
+                [block] => { // a four of the defender, that has to be blocked before the threat goes on
+                    let line = match board.set_square_from_index(block, SquareState::Filled(attacker)) {
+                        Ok(TurnState::Victory(_)) => Some(vec![]),
+                        Ok(TurnState::Continue) => self.defend(board, attacker, defender, depth, only_fours),
+                        _ => None
+                    };
+                    board.undo();
+                    line.map(|line| [vec![block], line].concat())
+                }
+                _ => None
+            };
+            board.undo();
+            match line {
+                Some(line) => {
+                    main_line.get_or_insert([vec![reply], line].concat());
+                }
+                None => return None
+            }
+        }
+        Some(main_line.unwrap_or_default()) // no reply can stop it
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+    use crate::game::ai::solver::{GameValue, Solver};
+
+    #[test]
+    fn a_threat_sequence_is_never_refuted() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut wins_found = 0;
+        for (x_size, y_size, seq_to_win) in [(4, 4, 3), (4, 3, 3), (4, 4, 4)] {
+            let mut solver = Solver::new(x_size, y_size);
+            for _ in 0..20 { // positions from random games, checked after every move
+                let mut board = TicTacToe::new(x_size, y_size, seq_to_win, ' ');
+                let (mut symbol, mut op_symbol) = ('X', 'O');
+                while !board.is_finished() {
+                    let mut search = ThreatSpaceSearch::new(&board, DEFAULT_THREAT_DEPTH);
+                    if let Some(line) = search.find_win(&board, symbol, op_symbol) {
+                        wins_found += 1;
+                        assert!(matches!(solver.solve(&board, symbol, op_symbol).0, GameValue::Win(_)), "{}", board);
+                        let mut after = board.clone();
+                        if after.set_square_from_index(line[0], SquareState::Filled(symbol)).unwrap() == TurnState::Continue {
+                            assert!(matches!(solver.solve(&after, op_symbol, symbol).0, GameValue::Loss(_)), "{}", after);
+                        }
+                    }
+                    let empty: Vec<usize> = (0..board.size()).filter(|i| board.squares[*i] == SquareState::None).collect();
+                    board.set_square_from_index(*empty.choose(&mut rng).unwrap(), SquareState::Filled(symbol)).unwrap();
+                    (symbol, op_symbol) = (op_symbol, symbol);
+                }
+            }
+        }
+        assert!(wins_found > 0);
+    }
+}
\ No newline at end of file
@@ -1,14 +1,99 @@
+use std::cmp::Reverse;
+use std::mem::size_of;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
-use crate::game::ai::node::Node;
-use crate::game::tictactoe_core::{SpecialBoardChecks, SquareState, TicTacToe, TurnState};
+use rand::{Rng, SeedableRng};
+use crate::game::ai::heuristic_weights::HeuristicWeights;
+use crate::game::ai::opening_book::OpeningBook;
+use crate::game::ai::tablebase::Tablebase;
+use crate::game::ai::search_stats::{MoveSource, SearchStats};
+use crate::game::ai::threat_space::{ThreatSpaceSearch, DEFAULT_THREAT_DEPTH};
+use crate::game::ai::solver::{GameValue, Solver};
+use crate::game::ai::transposition_table::{Bound, TableEntry, TranspositionTable, DEFAULT_MEMORY};
+use crate::game::tictactoe_core::{SpecialBoardChecks, SquareState, ThreatAnalysis, TicTacToe, TurnState};
+
+pub type Score = i32;
+pub const WIN_SCORE: Score = 1_000_000_000; // a win n moves after the root scores WIN_SCORE - n
+const INFINITY: Score = WIN_SCORE + 1;
+const HEURISTIC_SCALE: f32 = 10000.0; // keeps 4 decimal places of the heuristic
+pub const DEFAULT_CANDIDATE_RADIUS: usize = 2;
+pub const DEFAULT_SEED: u64 = 0; // every new Ai starts with it, so an Ai nobody gives a seed to still plays the same way every run
+
+#[derive(Copy, Clone)]
+struct ScoredMove {
+    index: usize,
+    score: Option<Score> // None until the move gets scored
+}
+
+// hands out the move given as first, then the killer moves, then the rest by their history,
+// only looking for the next best move when it's needed, since most nodes get cut after a few moves
+struct MoveList {
+    moves: Vec<(ScoredMove, u32)>, // with how soon they should be searched
+    next: usize
+}
+
+impl MoveList {
+    fn new(moves: Vec<ScoredMove>, first_move: Option<usize>, killers: [Option<usize>; 2], history: &[u32]) -> MoveList {
+        let moves = moves.into_iter().map(|m| {
+            let priority = if Some(m.index) == first_move {
+                u32::MAX
+            }else if Some(m.index) == killers[0] {
+                u32::MAX - 1
+            }else if Some(m.index) == killers[1] {
+                u32::MAX - 2
+            }else {
+                history.get(m.index).map_or(0, |h| *h.min(&(u32::MAX - 3)))
+            };
+            (m, priority)
+        }).collect();
+        MoveList { moves, next: 0 }
+    }
+}
+
+impl Iterator for MoveList {
+    type Item = ScoredMove;
+
+    fn next(&mut self) -> Option<ScoredMove> {
+        // the first of the highest, so moves with the same priority keep their order
+        let (i, _) = self.moves[self.next..].iter().enumerate().rev().max_by_key(|(_, (_, priority))| *priority)?;
+        self.moves[self.next..=self.next + i].rotate_right(1);
+        self.next += 1;
+        Some(self.moves[self.next - 1].0)
+    }
+}
 
 #[derive(Clone)]
 pub struct Ai{
     max_moves_to_analyze: usize,
     max_layers: usize,
     pub symbol: char,
-    pub op_symbol: char
+    pub op_symbol: char,
+    transposition_table: TranspositionTable,
+    deadline: Option<Instant>,
+    search_aborted: bool,
+    last_search: SearchStats,
+    nodes: usize,
+    beta_cutoffs: usize,
+    max_ply: usize,
+    exact_solver: bool,
+    threads: usize,
+    candidate_radius: usize, // only squares this close to a filled one get searched, 0 searches every empty square
+    weights: HeuristicWeights,
+    opening_book: Option<Arc<OpeningBook>>, // shared, so cloning the Ai every turn doesn't copy it
+    tablebase: Option<Arc<Tablebase>>,
+    random_moves: bool, // shuffles equally ordered moves, so the same position isn't always played the same way
+    move_ordering: bool, // killer moves and history first, instead of shuffling the moves
+    compare_move_ordering: bool, // searches again without move ordering after every move, to see how many nodes it saves
+    killers: Vec<[Option<usize>; 2]>, // the last two moves that caused a cutoff at each ply
+    history: [Vec<u32>; 2], // how often each square caused a cutoff and how deep, for the opponent and for the Ai
+    threat_depth: usize, // most threes in the threat sequences looked for before searching, 0 doesn't look for them
+    root_moves: Option<Vec<usize>>, // searched first at the root, the moves that stop the opponent's threats
+    seed: u64,
+    rng: StdRng
 }
 
 impl Ai {
@@ -17,100 +102,535 @@ impl Ai {
             max_moves_to_analyze,
             max_layers,
             symbol,
-            op_symbol
+            op_symbol,
+            transposition_table: TranspositionTable::new(DEFAULT_MEMORY),
+            deadline: None,
+            search_aborted: false,
+            last_search: SearchStats::default(),
+            nodes: 0,
+            beta_cutoffs: 0,
+            max_ply: 0,
+            exact_solver: false,
+            threads: 1,
+            candidate_radius: DEFAULT_CANDIDATE_RADIUS,
+            weights: HeuristicWeights::default(),
+            opening_book: None,
+            tablebase: None,
+            random_moves: true,
+            move_ordering: true,
+            compare_move_ordering: false,
+            killers: vec![],
+            history: [vec![], vec![]],
+            threat_depth: DEFAULT_THREAT_DEPTH,
+            root_moves: None,
+            seed: DEFAULT_SEED,
+            rng: StdRng::seed_from_u64(0)
         }
     }
-    pub fn act(&mut self, current_board: TicTacToe) -> (usize, usize) {
-        let mut root = Node::new((current_board.clone(), 0, TurnState::Continue));
-        let (_, index) = self.compute_next_move(&mut root, f32::NEG_INFINITY, f32::INFINITY, self.max_layers, true);
-        return current_board.get_index_coord(index);
+    fn create_helper(&self, memory_budget: usize) -> Ai { // a copy of the settings with its own table, to search in another thread
+        let mut helper = Ai {
+            transposition_table: TranspositionTable::new(memory_budget),
+            ..Ai::create(self.max_moves_to_analyze, self.max_layers, self.symbol, self.op_symbol)
+        };
+        helper.candidate_radius = self.candidate_radius;
+        helper.weights = self.weights;
+        helper.random_moves = self.random_moves;
+        helper.move_ordering = self.move_ordering;
+        helper.transposition_table.clear();
+        helper
+    }
+    pub fn set_transposition_table_memory(&mut self, bytes: usize) {
+        self.transposition_table.set_memory_budget(bytes)
+    }
+    pub fn set_exact_solver(&mut self, exact_solver: bool) { // only used on boards small enough to be solved
+        self.exact_solver = exact_solver
+    }
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1)
+    }
+    pub fn set_candidate_radius(&mut self, radius: usize) {
+        self.candidate_radius = radius
     }
-    fn compute_next_move(&self, current_node: &Node<(TicTacToe, usize, TurnState)>, mut parent_alpha: f32, mut parent_beta: f32, layer: usize, maximizing: bool) -> (f32, usize) {
-        if layer == 0 || current_node.data.2 != TurnState::Continue {
-            return (current_node.data_score, current_node.data.1);
+    pub fn candidate_radius(&self) -> usize {
+        self.candidate_radius
+    }
+    pub fn set_opening_book(&mut self, opening_book: Option<Arc<OpeningBook>>) {
+        self.opening_book = opening_book
+    }
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase
+    }
+    pub fn set_heuristic_weights(&mut self, weights: HeuristicWeights) {
+        self.weights = weights
+    }
+    pub fn set_random_moves(&mut self, random_moves: bool) { // without them any amount of threads plays the same moves
+        self.random_moves = random_moves
+    }
+    pub fn set_compare_move_ordering(&mut self, compare_move_ordering: bool) {
+        self.compare_move_ordering = compare_move_ordering
+    }
+    pub fn set_threat_depth(&mut self, threat_depth: usize) {
+        self.threat_depth = threat_depth
+    }
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed
+    }
+    pub fn last_search_stats(&self) -> &SearchStats {
+        &self.last_search
+    }
+    pub fn act(&mut self, current_board: TicTacToe, time_budget: Option<Duration>) -> (usize, usize) {
+        let start = Instant::now();
+        // the same seed and position always give the same move, no matter how many moves were made before,
+        // as long as there is no time budget to cut the search at a different point
+        self.rng = StdRng::seed_from_u64(self.seed ^ current_board.position_hash());
+        if let Some(book) = &self.opening_book {
+            let reply = book.pick(&current_board, self.symbol, &mut self.rng);
+            if let Some(index) = reply.filter(|index| current_board.squares[*index] == SquareState::None) {
+                let coord = current_board.get_index_coord(index);
+                self.last_search = SearchStats { search_time: start.elapsed(), principal_variation: vec![coord], source: MoveSource::OpeningBook, ..Default::default() };
+                return coord
+            }
+        }
+        if let Some(tablebase) = &self.tablebase {
+            if let Some((index, value)) = tablebase.best_move(&current_board, self.symbol, &mut self.rng) {
+                let coord = current_board.get_index_coord(index);
+                self.last_search = SearchStats { search_time: start.elapsed(), game_value: Some(value), principal_variation: vec![coord], source: MoveSource::Tablebase, ..Default::default() };
+                return coord
+            }
+        }
+        if self.exact_solver && Solver::can_solve(&current_board) {
+            let mut solver = Solver::new(current_board.x_size, current_board.y_size);
+            let (value, best_move) = solver.solve(&current_board, self.symbol, self.op_symbol);
+            if let Some(index) = best_move {
+                let depth = current_board.size() - current_board.filled;
+                self.last_search = SearchStats {
+                    depth_reached: depth,
+                    max_depth: depth,
+                    nodes: solver.nodes,
+                    search_time: start.elapsed(),
+                    time_budget,
+                    game_value: Some(value),
+                    principal_variation: vec![current_board.get_index_coord(index)],
+                    ..Default::default()
+                };
+                return current_board.get_index_coord(index)
+            }
+        }
+        self.root_moves = None;
+        if self.threat_depth > 0 {
+            let mut threat_search = ThreatSpaceSearch::new(&current_board, self.threat_depth);
+            if let Some(line) = threat_search.find_win(&current_board, self.symbol, self.op_symbol) {
+                let coord = current_board.get_index_coord(line[0]);
+                self.last_search = SearchStats {
+                    nodes: threat_search.nodes,
+                    search_time: start.elapsed(),
+                    time_budget,
+                    principal_variation: line.iter().map(|index| current_board.get_index_coord(*index)).collect(),
+                    source: MoveSource::ThreatSpace,
+                    ..Default::default()
+                };
+                return coord
+            }
+            self.root_moves = threat_search.defenses(&current_board, self.symbol, self.op_symbol);
         }
-        let possible_moves = self.get_possible_moves(&current_node.data.0, maximizing);
-        return if maximizing {
-            let mut best_move: (f32, usize) = (f32::NEG_INFINITY, 0);
+        let mut board = current_board.clone(); // every move gets played and taken back on this one board
+        let (best_move, depth_reached, finished_nodes, helpers) = self.search(&mut board, start, time_budget);
+        let search_time = start.elapsed(); // the comparison without move ordering doesn't count
+        let move_ordering_nodes = (self.compare_move_ordering && self.move_ordering && depth_reached > 0)
+            .then(|| (finished_nodes, self.unordered_nodes(&current_board, depth_reached)));
+        self.last_search = SearchStats {
+            depth_reached,
+            max_depth: self.max_ply,
+            nodes: self.nodes,
+            beta_cutoffs: self.beta_cutoffs,
+            search_time,
+            time_budget,
+            game_value: Ai::score_to_game_value(best_move.0),
+            root_score: Some(best_move.0),
+            principal_variation: self.principal_variation(&current_board, best_move.1, depth_reached, &helpers),
+            move_ordering_nodes,
+            ..Default::default()
+        };
+        current_board.get_index_coord(best_move.1)
+    }
+    // iterative deepening up to max_layers, gives the best move with its score, the deepest finished iteration,
+    // the nodes it took to finish it and the helpers that searched in other threads
+    fn search(&mut self, board: &mut TicTacToe, start: Instant, time_budget: Option<Duration>) -> ((Score, usize), usize, usize, Vec<Ai>) {
+        self.transposition_table.allocate(); // kept from the turns before, the forced results found then still hold
+        self.search_aborted = false;
+        (self.nodes, self.beta_cutoffs, self.max_ply) = (0, 0, 0);
+        self.clear_move_ordering(board.size());
+        self.deadline = None; // the first iteration always finishes, so there is always a move to return
+        let mut best_move = (0, 0);
+        let mut depth_reached = 0;
+        let mut finished_nodes = 0;
+        let threads = self.threads.min(board.size() - board.filled);
+        let mut helpers: Vec<Ai> = if threads > 1 {
+            (0..threads).map(|_| self.create_helper(self.transposition_table.capacity() * size_of::<Option<TableEntry>>() / threads)).collect()
+        }else {
+            vec![]
+        };
+        for helper in helpers.iter_mut() {
+            helper.clear_move_ordering(board.size());
+        }
+        for depth in 1..=self.max_layers {
+            let result = if helpers.is_empty() {
+                self.compute_next_move(board, -INFINITY, INFINITY, depth, true, 0)
+            }else {
+                self.compute_root_in_parallel(&mut helpers, board, depth, (depth > 1).then_some(best_move.1))
+            };
+            if self.search_aborted {
+                break
+            }
+            best_move = result;
+            depth_reached = depth;
+            finished_nodes = self.nodes + helpers.iter().map(|helper| helper.nodes).sum::<usize>();
+            self.deadline = time_budget.map(|budget| start + budget);
+            if self.time_is_up() || Ai::score_to_game_value(best_move.0).is_some() { // a forced result won't change by looking deeper
+                break
+            }
+        }
+        for helper in helpers.iter() {
+            self.nodes += helper.nodes;
+            self.beta_cutoffs += helper.beta_cutoffs;
+            self.max_ply = self.max_ply.max(helper.max_ply);
+        }
+        (best_move, depth_reached, finished_nodes, helpers)
+    }
+    // the nodes the same search takes to reach the given depth without killer moves and history, with the moves in random order
+    fn unordered_nodes(&self, board: &TicTacToe, depth: usize) -> usize {
+        let mut unordered = self.create_helper(self.transposition_table.capacity() * size_of::<Option<TableEntry>>());
+        unordered.max_layers = depth;
+        unordered.threads = self.threads;
+        unordered.move_ordering = false;
+        unordered.root_moves = self.root_moves.clone();
+        unordered.rng = StdRng::seed_from_u64(self.seed ^ board.position_hash());
+        unordered.search(&mut board.clone(), Instant::now(), None);
+        unordered.nodes
+    }
+    // the best k moves with their scores, every move gets searched with a full window so the scores are exact
+    pub fn rank_moves(&mut self, current_board: &TicTacToe, k: usize, time_budget: Option<Duration>) -> Vec<((usize, usize), Score)> {
+        let start = Instant::now();
+        self.rng = StdRng::seed_from_u64(self.seed ^ current_board.position_hash());
+        self.transposition_table.clear();
+        self.search_aborted = false;
+        self.deadline = None;
+        self.clear_move_ordering(current_board.size());
+        self.root_moves = None;
+        let mut board = current_board.clone();
+        let possible_moves = self.get_possible_moves(&mut board, true, 1);
+        let mut ranking = vec![];
+        for depth in 1..=self.max_layers {
+            let mut scores = vec![];
             for m in possible_moves.iter() {
-                let (childs_best, _) = self.compute_next_move(m, parent_alpha, parent_beta, layer - 1, false);
+                let (score, _) = self.search_move(&mut board, *m, -INFINITY, INFINITY, depth - 1, false, 1);
+                if self.search_aborted {
+                    break
+                }
+                scores.push((score, m.index));
+            }
+            if self.search_aborted {
+                break
+            }
+            ranking = scores;
+            self.deadline = time_budget.map(|budget| start + budget);
+            if self.time_is_up() {
+                break
+            }
+        }
+        ranking.sort_by_key(|m| Reverse(m.0)); // stable, so equal scores keep the move order
+        ranking.into_iter().take(k).map(|(score, index)| (current_board.get_index_coord(index), score)).collect()
+    }
+    pub fn score_to_game_value(score: Score) -> Option<GameValue> { // None unless the score is a forced win or loss
+        if score > WIN_SCORE / 2 {
+            Some(GameValue::Win((WIN_SCORE - score) as usize))
+        }else if score < -WIN_SCORE / 2 {
+            Some(GameValue::Loss((WIN_SCORE + score) as usize))
+        }else {
+            None
+        }
+    }
+    // every thread searches its share of the root moves, given in the same order the single threaded search uses,
+    // and the first move with the highest score wins, so both always pick the same move
+    fn compute_root_in_parallel(&mut self, helpers: &mut [Ai], board: &TicTacToe, depth: usize, previous_best: Option<usize>) -> (Score, usize) {
+        let possible_moves = self.get_possible_moves(&mut board.clone(), true, 1);
+        let moves: &Vec<ScoredMove> = &MoveList::new(possible_moves, previous_best, [None; 2], &[]).collect();
+        let thread_amount = helpers.len();
+        let shared_best = &AtomicI32::new(-INFINITY); // the best exact score found by any thread, used to cut the others
+        let results: Vec<(Score, usize)> = thread::scope(|scope| {
+            let handles: Vec<_> = helpers.iter_mut().enumerate().map(|(t, helper)| {
+                helper.deadline = self.deadline;
+                helper.rng = StdRng::seed_from_u64(self.rng.gen());
+                let mut board = board.clone();
+                scope.spawn(move || {
+                    let mut best: (Score, usize) = (-INFINITY, usize::MAX);
+                    for (i, m) in moves.iter().enumerate().skip(t).step_by(thread_amount) {
+                        // one below the other threads best, so a move that ties with it still gets its exact score
+                        let alpha = best.0.max(shared_best.load(Ordering::Relaxed) - 1);
+                        let (childs_best, _) = helper.search_move(&mut board, *m, alpha, INFINITY, depth - 1, false, 1);
+                        if helper.search_aborted {
+                            break
+                        }
+                        if best.0 < childs_best {
+                            best = (childs_best, i)
+                        }
+                        if childs_best > alpha {
+                            shared_best.fetch_max(childs_best, Ordering::Relaxed);
+                        }
+                    }
+                    best
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        if helpers.iter().any(|helper| helper.search_aborted) {
+            self.search_aborted = true;
+            return (0, 0)
+        }
+        let (score, i) = results.into_iter().fold((-INFINITY, usize::MAX), |best, result| {
+            if result.0 > best.0 || (result.0 == best.0 && result.1 < best.1) { result } else { best }
+        });
+        (score, moves[i].index)
+    }
+    // follows the best moves stored in the tables, in parallel searches the root moves are only in the helpers tables
+    fn principal_variation(&self, board: &TicTacToe, first_move: usize, max_length: usize, helpers: &[Ai]) -> Vec<(usize, usize)> {
+        let mut board = board.clone();
+        let mut variation = vec![];
+        let mut next_move = Some(first_move);
+        let mut own_turn = true;
+        while let Some(index) = next_move {
+            let symbol = if own_turn { self.symbol } else { self.op_symbol };
+            match board.set_square_from_index(index, SquareState::Filled(symbol)) {
+                Ok(state) => {
+                    variation.push(board.get_index_coord(index));
+                    if state != TurnState::Continue {
+                        break
+                    }
+                }
+                Err(_) => break
+            }
+            if variation.len() >= max_length {
+                break
+            }
+            own_turn = !own_turn;
+            let key = board.position_hash();
+            next_move = std::iter::once(self).chain(helpers).find_map(|ai| ai.transposition_table.probe(key)).map(|entry| entry.best_move);
+        }
+        variation
+    }
+    fn time_is_up(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+    fn square_state(&self, own_turn: bool) -> SquareState {
+        if own_turn {
+            SquareState::Filled(self.symbol)
+        }else {
+            SquareState::Filled(self.op_symbol)
+        }
+    }
+    // plays the move, searches the position after it and takes the move back, leaving the board as it was
+    #[allow(clippy::too_many_arguments)]
+    fn search_move(&mut self, board: &mut TicTacToe, m: ScoredMove, alpha: Score, beta: Score, layer: usize, maximizing: bool, ply: usize) -> (Score, usize) {
+        let own_turn = !maximizing; // the move was made by the side that isn't moving next
+        let square_state = self.square_state(own_turn);
+        let move_state = board.set_square_from_index(m.index, square_state).unwrap(); // moves are always empty squares of an unfinished board
+        let result = if layer == 0 || move_state != TurnState::Continue {
+            self.nodes += 1;
+            self.max_ply = self.max_ply.max(ply);
+            let score = m.score.unwrap_or_else(|| self.evaluate(board, square_state, m.index, move_state, own_turn, ply));
+            (score, m.index)
+        }else {
+            self.compute_next_move(board, alpha, beta, layer, maximizing, ply)
+        };
+        board.undo();
+        result
+    }
+    fn compute_next_move(&mut self, board: &mut TicTacToe, mut parent_alpha: Score, mut parent_beta: Score, layer: usize, maximizing: bool, ply: usize) -> (Score, usize) {
+        self.nodes += 1;
+        self.max_ply = self.max_ply.max(ply);
+        if self.search_aborted || self.time_is_up() {
+            self.search_aborted = true;
+            return (0, 0)
+        }
+        let key = board.position_hash();
+        let mut table_move = None;
+        if let Some(entry) = self.transposition_table.probe(key) {
+            // leaf scores depend on the last move played and not only on the position, so only forced results
+            // are the same however the position is reached, and only they get reused, at any depth since looking deeper won't change them
+            let forced = Ai::score_to_game_value(entry.score).is_some();
+            if forced {
+                let score = Ai::from_table_score(entry.score, ply);
+                match entry.bound {
+                    Bound::Exact => return (score, entry.best_move),
+                    Bound::Lower => parent_alpha = parent_alpha.max(score),
+                    Bound::Upper => parent_beta = parent_beta.min(score)
+                }
+                if parent_beta <= parent_alpha {
+                    return (score, entry.best_move)
+                }
+            }
+            if forced || ply == 0 { // the root still searches the best move of the last iteration first
+                table_move = Some(entry.best_move);
+            }
+        }
+        let (alpha, beta) = (parent_alpha, parent_beta);
+        let possible_moves = self.get_possible_moves(board, maximizing, ply + 1);
+        let killers = self.killers.get(ply).copied().unwrap_or([None; 2]);
+        let moves = MoveList::new(possible_moves, table_move, killers, &self.history[maximizing as usize]); // searches the best move found before first
+        let best_move = if maximizing {
+            let mut best_move: (Score, usize) = (-INFINITY, 0);
+            for m in moves {
+                let (childs_best, _) = self.search_move(board, m, parent_alpha, parent_beta, layer - 1, false, ply + 1);
                 if best_move.0 < childs_best {
-                    best_move = (childs_best, m.data.1)
+                    best_move = (childs_best, m.index)
                 }
                 parent_alpha = parent_alpha.max(best_move.0);
                 if parent_beta <= parent_alpha {
+                    self.beta_cutoffs += 1;
+                    self.record_cutoff(m.index, layer, maximizing, ply);
                     break
                 }
             }
             best_move
         } else {
-            let mut best_move: (f32, usize) = (f32::INFINITY, 0);
-            for m in possible_moves.iter() {
-                let (childs_best, _) = self.compute_next_move(m, parent_alpha, parent_beta, layer - 1, true);
+            let mut best_move: (Score, usize) = (INFINITY, 0);
+            for m in moves {
+                let (childs_best, _) = self.search_move(board, m, parent_alpha, parent_beta, layer - 1, true, ply + 1);
                 if best_move.0 > childs_best {
-                    best_move = (childs_best, m.data.1)
+                    best_move = (childs_best, m.index)
                 }
                 parent_beta = parent_beta.min(best_move.0);
                 if parent_beta <= parent_alpha {
+                    self.beta_cutoffs += 1;
+                    self.record_cutoff(m.index, layer, maximizing, ply);
                     break
                 }
             }
             best_move
+        };
+        if self.search_aborted {
+            return best_move
+        }
+        let bound = if best_move.0 <= alpha {
+            Bound::Upper
+        }else if best_move.0 >= beta {
+            Bound::Lower
+        }else {
+            Bound::Exact
+        };
+        self.transposition_table.store(key, layer, Ai::to_table_score(best_move.0, ply), bound, best_move.1);
+        best_move
+    }
+
+    // wins and losses are stored as the moves left from the position, so they stay right when it's reached at another ply or turn
+    fn to_table_score(score: Score, ply: usize) -> Score {
+        if score > WIN_SCORE / 2 {
+            score + ply as Score
+        }else if score < -WIN_SCORE / 2 {
+            score - ply as Score
+        }else {
+            score
+        }
+    }
+
+    fn from_table_score(score: Score, ply: usize) -> Score {
+        if score > WIN_SCORE / 2 {
+            score - ply as Score
+        }else if score < -WIN_SCORE / 2 {
+            score + ply as Score
+        }else {
+            score
+        }
+    }
+
+    fn clear_move_ordering(&mut self, board_size: usize) {
+        self.killers.clear();
+        self.history = [vec![0; board_size], vec![0; board_size]];
+    }
+
+    // a move that was good enough to cut a position is likely good in the positions next to it too
+    fn record_cutoff(&mut self, index: usize, layer: usize, own_turn: bool, ply: usize) {
+        if !self.move_ordering {
+            return
+        }
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; 2]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(index) {
+            killers[1] = killers[0];
+            killers[0] = Some(index);
+        }
+        if let Some(history) = self.history[own_turn as usize].get_mut(index) {
+            *history = history.saturating_add((layer * layer) as u32); // cutoffs far from the leaves save more
         }
     }
 
-    fn sort_moves<T>(moves: &mut Vec<Node<T>>, ascending: bool) where T: PartialEq {
+    fn sort_moves(moves: &mut [ScoredMove], ascending: bool) { // stable, so the moves that get cut among equal scores don't depend on the sort
         if ascending {
-            moves.sort_unstable_by(|a, b| { a.partial_cmp(b).unwrap() });
+            moves.sort_by(|a, b| { a.score.cmp(&b.score) });
         }else {
-            moves.sort_unstable_by(|a, b| { b.partial_cmp(a).unwrap() });
+            moves.sort_by(|a, b| { b.score.cmp(&a.score) });
         }
     }
 
-    fn get_possible_moves(&self, current_board: &TicTacToe, own_turn: bool) -> Vec<Node<(TicTacToe, usize, TurnState)>>{
-        let mut moves = vec![];
-        for square in current_board.squares.iter().enumerate() {
-            if let SquareState::None = square.1 {
-                let move_state: TurnState;
-                let square_state;
+    // score of the position after the move, from the point of view of the Ai
+    fn evaluate(&self, board: &TicTacToe, square_state: SquareState, index: usize, move_state: TurnState, own_turn: bool, ply: usize) -> Score {
+        match move_state {
+            TurnState::Draw => 0,
+            TurnState::Victory(_) => { // the sooner the win the higher the score, so it's never dragged out
                 if own_turn {
-                    square_state = SquareState::Filled(self.symbol);
+                    WIN_SCORE - ply as Score
                 }else {
-                    square_state = SquareState::Filled(self.op_symbol);
+                    -(WIN_SCORE - ply as Score)
                 }
-                let mut board_binding = current_board.clone();
-                move_state = board_binding.set_square_from_index(square.0, square_state);
-                let mut possible_move_node = Node::new((board_binding, square.0, move_state.clone()));
-                match move_state {
-                    TurnState::Draw => {
-                        possible_move_node.data_score = 0.0;
-                    }
-                    TurnState::Victory => {
-                        if own_turn {
-                            possible_move_node.data_score = 1.0;
-                        }else {
-                            possible_move_node.data_score = -1.0;
-                        }
-                    }
-                    TurnState::Continue => {
-                        possible_move_node.data_score = self.get_move_heuristic(&possible_move_node.data.0, square_state, square.0, own_turn)
-                    }
-                    _ => {
-                        panic!("Unexpected Ai Error")
-                    }
-                }
-                moves.push(possible_move_node);
             }
+            TurnState::Continue => self.get_move_heuristic(board, square_state, index, own_turn)
+        }
+    }
+
+    fn score_move(&self, board: &mut TicTacToe, index: usize, own_turn: bool, ply: usize) -> Score {
+        let square_state = self.square_state(own_turn);
+        let move_state = board.set_square_from_index(index, square_state).unwrap();
+        let score = self.evaluate(board, square_state, index, move_state, own_turn, ply);
+        board.undo();
+        score
+    }
+
+    // the moves only get scored when some have to be cut, otherwise a move is scored once it's searched as a leaf
+    fn get_possible_moves(&mut self, board: &mut TicTacToe, own_turn: bool, ply: usize) -> Vec<ScoredMove> {
+        let candidates = if self.candidate_radius > 0 {
+            board.candidate_moves(self.candidate_radius)
+        }else {
+            (0..board.size()).filter(|i| board.squares[*i] == SquareState::None).collect()
+        };
+        let mut moves: Vec<ScoredMove> = candidates.into_iter().map(|index| ScoredMove { index, score: None }).collect();
+        if self.random_moves && self.move_ordering {
+            moves.shuffle(&mut self.rng); // before the sort, so only moves with the same score get mixed
         }
         if self.max_moves_to_analyze > 0 && moves.len() > self.max_moves_to_analyze {
+            for m in moves.iter_mut() {
+                m.score = Some(self.score_move(board, m.index, own_turn, ply));
+            }
             Ai::sort_moves(&mut moves, !own_turn);
-            moves = moves.split_at(self.max_moves_to_analyze).0.to_owned(); // cuts the amount of possible moves, keeping only the best ones
+            moves.truncate(self.max_moves_to_analyze); // cuts the amount of possible moves, keeping only the best ones
+        }
+        if self.random_moves && !self.move_ordering {
+            moves.shuffle(&mut self.rng);
+        }
+        if let Some(root_moves) = self.root_moves.as_ref().filter(|_| ply == 1) {
+            // only ordered, the threat search can miss a threat, so the other moves still get searched after them
+            for index in root_moves.iter() {
+                if !moves.iter().any(|m| m.index == *index) {
+                    moves.push(ScoredMove { index: *index, score: None });
+                }
+            }
+            moves.sort_by_key(|m| !root_moves.contains(&m.index)); // stable, so both groups keep their order
         }
-        moves.shuffle(&mut thread_rng());
         moves
     }
 
-    pub fn get_move_heuristic(&self, board: &TicTacToe, square_state: SquareState, index: usize, own_turn: bool) -> f32 {
+    pub fn get_move_heuristic(&self, board: &TicTacToe, square_state: SquareState, index: usize, own_turn: bool) -> Score {
         /*
         THE FULL HEURISTIC IS BASED ON:
             - Amount of same symbols in winnable distance in same line, column and diagonals
@@ -118,20 +638,24 @@ impl Ai {
             - Amount of available axis to play after the move, that way he prioritizes spaces in the middle->cornes->center edges
             - Amount of empty spaces around the placed space
             - Position of the move, if its on the center edges, the empty spaces sum gets cleared, removing from the full score
+            - Amount of squares the move leaves one move from winning, more than one being a fork
         */
-        let attack_score = board.sum_squares_in_winnable_distance(index, square_state, false) as f32;
+        let weights = &self.weights;
+        let attack_score = board.sum_squares_in_winnable_distance(index, square_state, false) as f32 * weights.attack;
         let available_axis = board.check_n_of_available_axis(index, square_state) as f32;
+        let symbol = if own_turn { self.symbol } else { self.op_symbol };
+        let threat_score = board.winning_square_count_through(index, symbol) as f32 * weights.threat;
         let op_square = if own_turn { SquareState::Filled(self.op_symbol) } else { SquareState::Filled(self.symbol) };
         let mut total_defense_score = board.sum_squares_in_winnable_distance(index, op_square, false) as f32;
         let mut empty_space_around_score = board.spaces_of_around(index, SquareState::None) as i32;
         let x_size = board.x_size;
         let y_size = board.y_size;
         let coord = board.get_index_coord(index);
-        if board.seq_to_win.pow(2) < board.size() {
+        if board.seq_to_win < x_size.max(y_size) {
             let highest_amount_of_blocked_spaces = board.sum_squares_in_winnable_distance(index, op_square, true) as f32;
             if (board.seq_to_win % 2 != 0 && highest_amount_of_blocked_spaces >= (board.seq_to_win as f32 / 2.0).ceil()) ||
                 (board.seq_to_win % 2 == 0 && highest_amount_of_blocked_spaces >= (board.seq_to_win as f32 - 2.0)) {
-                total_defense_score *= 100.0;
+                total_defense_score *= weights.block_bonus;
             }
         }
         if coord.1 == 0 || coord.1 == y_size - 1 {
@@ -141,12 +665,111 @@ impl Ai {
         } else if coord.0 == 0 || coord.0 == x_size - 1 {
             empty_space_around_score = 0;
         }
-        total_defense_score /= 10.0;
-        let heuristic = ((attack_score + total_defense_score + (available_axis / 10.0)) + (empty_space_around_score as f32 / 100.0)) / 100.0;
+        total_defense_score *= weights.defense;
+        let heuristic = ((attack_score + total_defense_score + threat_score + (available_axis * weights.available_axis)) + (empty_space_around_score as f32 * weights.empty_around)) * weights.overall;
+        let heuristic = ((heuristic * HEURISTIC_SCALE).round() as Score).clamp(-WIN_SCORE / 4, WIN_SCORE / 4);
         return if own_turn {
             heuristic
         }else {
             -heuristic
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::ai::proof_number::ProofNumberSearch;
+
+    #[test]
+    fn threat_defenses_dont_leave_out_the_drawing_move() {
+        // only (3, 2) keeps the draw, the defenses the threat search finds all lose
+        let (board, symbol, op_symbol) = ProofNumberSearch::position_from_cells(4, 3, 3, "------x-x-o-").unwrap();
+        assert_eq!(Solver::new(4, 3).solve(&board, symbol, op_symbol).0, GameValue::Draw);
+        for seed in 0..5 {
+            let mut ai = Ai::create(10, 5, symbol, op_symbol);
+            ai.set_threat_depth(DEFAULT_THREAT_DEPTH);
+            ai.set_seed(seed);
+            let (x, y) = ai.act(board.clone(), None);
+            let mut after = board.clone();
+            after.set_square(x, y, SquareState::Filled(symbol)).unwrap();
+            assert_eq!(Solver::new(4, 3).solve(&after, op_symbol, symbol).0, GameValue::Draw, "seed {} played ({}, {})", seed, x, y);
+        }
+    }
+
+    #[test]
+    fn moves_match_the_recorded_search() {
+        // the moves and root scores of the search with the candidates sorted by score, equal scores in the order of the candidates,
+        // with the threat search, the threat weight and the move ordering turned off, so only the sort decides the ties
+        let positions = [
+            (5, 5, 4, 4, "-------------x-----------", (2, 1), -257),
+            (5, 5, 4, 4, "-----------x-------------", (1, 1), -248),
+            (7, 7, 5, 3, "----x---------o-o-------------------x-----------x", (2, 0), 357),
+            (6, 6, 4, 3, "-------------o---x------x-----------", (2, 1), 2136),
+            (6, 6, 4, 3, "---x----o----xx-oo-x--x-----o----x-o", (1, 0), 6313),
+            (6, 6, 4, 3, "------------------x----o------o---x-", (1, 3), 2135),
+            (6, 6, 4, 3, "-----------x--x--o--------------o--x", (4, 3), 4135),
+            (6, 6, 4, 3, "---------x-----o---x----------------", (2, 3), 3018),
+            (4, 4, 3, 5, "--o---x----o---x", (2, 2), WIN_SCORE - 3),
+            (9, 9, 5, 3, "-------------o--------------------------------x----------------------------------", (1, 3), 248)
+        ];
+        for (x_size, y_size, seq_to_win, layers, cells, expected_move, expected_score) in positions {
+            let (board, symbol, op_symbol) = ProofNumberSearch::position_from_cells(x_size, y_size, seq_to_win, cells).unwrap();
+            let mut ai = Ai::create(10, layers, symbol, op_symbol);
+            ai.random_moves = false;
+            ai.move_ordering = false;
+            ai.threat_depth = 0;
+            ai.weights.threat = 0.0;
+            assert_eq!(ai.act(board, None), expected_move, "{}", cells);
+            assert_eq!(ai.last_search_stats().root_score, Some(expected_score), "{}", cells);
+        }
+    }
+
+    #[test]
+    fn the_table_saves_nodes_and_keeps_them_for_the_next_turn() {
+        // 4x4 with 3 in a row is a forced win, the same winning positions get reached through many move orders
+        let create = |memory| {
+            let mut ai = Ai::create(10, 7, 'X', 'O');
+            ai.random_moves = false;
+            ai.threat_depth = 0;
+            ai.set_transposition_table_memory(memory);
+            ai
+        };
+        let (mut with_table, mut without_table) = (create(DEFAULT_MEMORY), create(1));
+        let board = TicTacToe::new(4, 4, 3, ' ');
+        let (x, y) = with_table.act(board.clone(), None);
+        assert_eq!(without_table.act(board.clone(), None), (x, y));
+        assert_eq!(with_table.last_search_stats().root_score, without_table.last_search_stats().root_score);
+        assert!(with_table.last_search_stats().nodes < without_table.last_search_stats().nodes);
+        let mut next_turn = board.clone();
+        next_turn.set_square(x, y, SquareState::Filled('X')).unwrap();
+        next_turn.set_square(0, 0, SquareState::Filled('O')).unwrap();
+        let mut new_ai = create(DEFAULT_MEMORY);
+        assert_eq!(with_table.act(next_turn.clone(), None), new_ai.act(next_turn, None));
+        assert!(with_table.last_search_stats().nodes < new_ai.last_search_stats().nodes);
+    }
+
+    #[test]
+    fn parallel_search_matches_one_thread() {
+        let positions = [
+            (4, 4, 3, 5, "----------------"),
+            (5, 5, 4, 4, "-------------x-----------"),
+            (6, 6, 4, 3, "---x----o----xx-oo-x--x-----o----x-o"),
+            (7, 7, 5, 3, "----x---------o-o-------------------x-----------x")
+        ];
+        for (x_size, y_size, seq_to_win, layers, cells) in positions {
+            let (board, symbol, op_symbol) = ProofNumberSearch::position_from_cells(x_size, y_size, seq_to_win, cells).unwrap();
+            let act = |threads| {
+                let mut ai = Ai::create(10, layers, symbol, op_symbol);
+                ai.set_random_moves(false);
+                ai.set_threads(threads);
+                let m = ai.act(board.clone(), None);
+                (m, ai.last_search_stats().root_score)
+            };
+            let single = act(1);
+            for threads in [2, 4] {
+                assert_eq!(act(threads), single, "{} with {} threads", cells, threads);
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+use std::mem::size_of;
+use crate::game::ai::tictactoe_ai_player::Score;
+
+pub const DEFAULT_MEMORY: usize = 8 * 1024 * 1024; // in bytes
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower, // the real score is at least the stored one
+    Upper // the real score is at most the stored one
+}
+
+#[derive(Copy, Clone)]
+pub struct TableEntry {
+    pub key: u64,
+    pub depth: usize,
+    pub score: Score,
+    pub bound: Bound,
+    pub best_move: usize,
+    generation: u8
+}
+
+#[derive(Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<TableEntry>>,
+    memory_budget: usize,
+    generation: u8 // entries from older searches are treated as empty, so clearing doesn't touch the memory
+}
+
+impl TranspositionTable {
+    pub fn new(memory_budget: usize) -> TranspositionTable {
+        TranspositionTable {
+            entries: vec![], // only allocated on the first clear, so cloned Ais stay cheap
+            memory_budget,
+            generation: 0
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        let max_entries = (self.memory_budget / size_of::<Option<TableEntry>>()).max(1);
+        1 << max_entries.ilog2() // power of two, so the index is just a mask of the key
+    }
+
+    pub fn set_memory_budget(&mut self, memory_budget: usize) {
+        self.memory_budget = memory_budget;
+        self.entries = vec![]
+    }
+
+    pub fn allocate(&mut self) { // keeps the stored entries, unless the memory budget changed
+        if self.entries.len() != self.capacity() {
+            self.entries = vec![None; self.capacity()]
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        if self.entries.len() != self.capacity() {
+            self.entries = vec![None; self.capacity()]
+        }else if self.generation == 0 {
+            self.entries.fill(None)
+        }
+    }
+
+    pub fn probe(&self, key: u64) -> Option<&TableEntry> {
+        if self.entries.is_empty() {
+            return None
+        }
+        match &self.entries[key as usize & (self.entries.len() - 1)] {
+            Some(entry) if entry.key == key && entry.generation == self.generation => Some(entry),
+            _ => None
+        }
+    }
+
+    pub fn store(&mut self, key: u64, depth: usize, score: Score, bound: Bound, best_move: usize) {
+        if self.entries.is_empty() {
+            return
+        }
+        let mask = self.entries.len() - 1;
+        let slot = &mut self.entries[key as usize & mask];
+        match slot {
+            // keeps the deeper search of the same position, any other position gets replaced
+            Some(old) if old.key == key && old.generation == self.generation && old.depth > depth => {}
+            _ => *slot = Some(TableEntry { key, depth, score, bound, best_move, generation: self.generation })
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::{Display, Formatter};
+use std::thread;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use crate::game::ai::heuristic_weights::HeuristicWeights;
+use crate::game::ai::tictactoe_ai_player::Ai;
+use crate::game::tictactoe_core::{MoveError, SquareState, TicTacToe, TurnState};
+
+// the medium difficulty, so thousands of games stay fast, every setting is given here so tuning runs don't change with the Ai defaults
+const TUNER_MOVES_TO_ANALYZE: usize = 6;
+const TUNER_LAYERS: usize = 2;
+const TUNER_THREAT_DEPTH: usize = 1;
+const TUNER_CANDIDATE_RADIUS: usize = 2;
+const TUNER_MEMORY: usize = 1024 * 1024;
+const MUTATION_STRENGTH: f32 = 0.5; // a mutated weight gets multiplied by up to e^0.5 or divided by it
+
+#[derive(Copy, Clone, Default)]
+pub struct MatchStats {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize
+}
+
+impl MatchStats {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+    pub fn score(&self) -> f32 { // draws count as half a win
+        (self.wins as f32 + self.draws as f32 / 2.0) / self.games().max(1) as f32
+    }
+    fn add(&mut self, other: MatchStats) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses
+    }
+}
+
+impl Display for MatchStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "W/D/L: {}/{}/{} ({:.1}%)", self.wins, self.draws, self.losses, self.score() * 100.0)
+    }
+}
+
+// local search: every generation mutates the best weights and keeps the mutation only if it beats them
+pub struct Tuner {
+    x_size: usize,
+    y_size: usize,
+    seq_to_win: usize,
+    pub games_per_match: usize,
+    pub threads: usize,
+    rng: StdRng
+}
+
+impl Tuner {
+    pub fn new(x_size: usize, y_size: usize, seq_to_win: usize, seed: u64) -> Option<Tuner> { // None if the board is too big
+        if !TicTacToe::fits_bitboard(x_size, y_size) {
+            return None
+        }
+        Some(Tuner {
+            x_size,
+            y_size,
+            seq_to_win,
+            games_per_match: 100,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            rng: StdRng::seed_from_u64(seed)
+        })
+    }
+
+    pub fn tune(&mut self, start: HeuristicWeights, generations: usize) -> Result<(HeuristicWeights, MatchStats), MoveError> {
+        let mut best = start;
+        for generation in 1..=generations {
+            let candidate = self.mutate(&best);
+            let stats = self.play_match(&candidate, &best)?;
+            let accepted = stats.score() > 0.5;
+            if accepted {
+                best = candidate
+            }
+            println!("Generation {}: candidate {}{}", generation, stats, if accepted { ", accepted" } else { "" });
+        }
+        let stats = self.play_match(&best, &start)?;
+        Ok((best, stats))
+    }
+
+    fn mutate(&mut self, weights: &HeuristicWeights) -> HeuristicWeights {
+        let mut mutated = *weights;
+        let amount = self.rng.gen_range(1..=2);
+        for name in HeuristicWeights::NAMES.choose_multiple(&mut self.rng, amount) {
+            *mutated.get_mut(name).unwrap() *= self.rng.gen_range(-MUTATION_STRENGTH..MUTATION_STRENGTH).exp();
+        }
+        mutated
+    }
+
+    // results are from the first weights point of view, each side starts half of the games, an illegal move from either Ai stops the match
+    pub fn play_match(&mut self, weights: &HeuristicWeights, op_weights: &HeuristicWeights) -> Result<MatchStats, MoveError> {
+        let seed: u64 = self.rng.gen(); // every game gets its own seeds, so the same tuner seed gives the same games
+        let threads = self.threads.clamp(1, self.games_per_match.max(1));
+        let tuner = &*self;
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads).map(|t| {
+                scope.spawn(move || {
+                    let mut ai = Tuner::create_ai(weights, 'X', 'O');
+                    let mut op_ai = Tuner::create_ai(op_weights, 'O', 'X');
+                    let mut stats = MatchStats::default();
+                    for game in (t..tuner.games_per_match).step_by(threads) {
+                        ai.set_seed(seed.wrapping_add(2 * game as u64));
+                        op_ai.set_seed(seed.wrapping_add(2 * game as u64 + 1));
+                        let winner = if game % 2 == 0 {
+                            tuner.play_game(&mut ai, &mut op_ai)?
+                        }else {
+                            tuner.play_game(&mut op_ai, &mut ai)?
+                        };
+                        match winner {
+                            Some('X') => stats.wins += 1,
+                            Some(_) => stats.losses += 1,
+                            None => stats.draws += 1
+                        }
+                    }
+                    Ok(stats)
+                })
+            }).collect();
+            let mut total = MatchStats::default();
+            for handle in handles {
+                total.add(handle.join().unwrap()?)
+            }
+            Ok(total)
+        })
+    }
+
+    fn create_ai(weights: &HeuristicWeights, symbol: char, op_symbol: char) -> Ai {
+        let mut ai = Ai::create(TUNER_MOVES_TO_ANALYZE, TUNER_LAYERS, symbol, op_symbol);
+        ai.set_heuristic_weights(*weights);
+        ai.set_transposition_table_memory(TUNER_MEMORY);
+        ai.set_threat_depth(TUNER_THREAT_DEPTH);
+        ai.set_candidate_radius(TUNER_CANDIDATE_RADIUS);
+        ai.set_threads(1); // the games already run in parallel
+        ai
+    }
+
+    fn play_game(&self, first: &mut Ai, second: &mut Ai) -> Result<Option<char>, MoveError> { // the symbol of the winner, None on a draw
+        let mut board = TicTacToe::new(self.x_size, self.y_size, self.seq_to_win, ' ');
+        let mut players = [first, second];
+        for turn in 0.. {
+            let player = &mut players[turn % 2];
+            let (x, y) = player.act(board.clone(), None);
+            match board.set_square(x, y, SquareState::Filled(player.symbol))? {
+                TurnState::Continue => {}
+                TurnState::Victory(_) => return Ok(Some(player.symbol)),
+                TurnState::Draw => return Ok(None)
+            }
+        }
+        Ok(None)
+    }
+}
\ No newline at end of file
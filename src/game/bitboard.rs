@@ -0,0 +1,144 @@
+use std::ops::{BitAnd, BitOr, Not, Shl, Shr};
+
+const WORDS: usize = 6;
+pub const MAX_BITS: usize = WORDS * 64; // enough for a 19x19 board with its padding column
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Bitboard {
+    words: [u64; WORDS]
+}
+
+impl Bitboard {
+    pub fn get(&self, bit: usize) -> bool {
+        self.words[bit / 64] & (1 << (bit % 64)) != 0
+    }
+
+    pub fn set(&mut self, bit: usize) {
+        self.words[bit / 64] |= 1 << (bit % 64)
+    }
+
+    pub fn unset(&mut self, bit: usize) {
+        self.words[bit / 64] &= !(1 << (bit % 64))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    pub fn has_run(&self, shift: usize, length: usize) -> bool {
+        // every bit left after n steps is the start of n+1 set bits spaced by the shift
+        let mut run = *self;
+        for _ in 1..length {
+            run = run & (run >> shift);
+            if run.is_empty() {
+                return false
+            }
+        }
+        !run.is_empty()
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Bitboard;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        let mut words = self.words;
+        for (w, r) in words.iter_mut().zip(rhs.words) {
+            *w &= r
+        }
+        Bitboard { words }
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Bitboard;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        let mut words = self.words;
+        for (w, r) in words.iter_mut().zip(rhs.words) {
+            *w |= r
+        }
+        Bitboard { words }
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Self::Output {
+        Bitboard { words: self.words.map(|w| !w) }
+    }
+}
+
+impl Shr<usize> for Bitboard {
+    type Output = Bitboard;
+
+    fn shr(self, rhs: usize) -> Self::Output {
+        let (word_shift, bit_shift) = (rhs / 64, rhs % 64);
+        let mut words = [0; WORDS];
+        for (i, word) in words.iter_mut().enumerate().take(WORDS.saturating_sub(word_shift)) {
+            *word = self.words[i + word_shift] >> bit_shift;
+            if bit_shift > 0 && i + word_shift + 1 < WORDS {
+                *word |= self.words[i + word_shift + 1] << (64 - bit_shift)
+            }
+        }
+        Bitboard { words }
+    }
+}
+
+
+impl Shl<usize> for Bitboard {
+    type Output = Bitboard;
+
+    fn shl(self, rhs: usize) -> Self::Output {
+        let (word_shift, bit_shift) = (rhs / 64, rhs % 64);
+        let mut words = [0; WORDS];
+        for (i, word) in words.iter_mut().enumerate().skip(word_shift) {
+            *word = self.words[i - word_shift] << bit_shift;
+            if bit_shift > 0 && i > word_shift {
+                *word |= self.words[i - word_shift - 1] >> (64 - bit_shift)
+            }
+        }
+        Bitboard { words }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(squares: &[(usize, usize)]) -> Bitboard { // on a 19x19 board, with the padding column at x = 19
+        let mut board = Bitboard::default();
+        for (x, y) in squares {
+            board.set(x + 20 * y);
+        }
+        board
+    }
+
+    #[test]
+    fn runs_are_found_in_every_direction_up_to_the_last_square() {
+        assert!(board(&[(1, 3), (2, 3), (3, 3), (4, 3), (5, 3)]).has_run(1, 5)); // across the first two words
+        assert!(board(&[(18, 14), (18, 15), (18, 16), (18, 17), (18, 18)]).has_run(20, 5));
+        assert!(board(&[(14, 14), (15, 15), (16, 16), (17, 17), (18, 18)]).has_run(21, 5));
+        assert!(board(&[(18, 14), (17, 15), (16, 16), (15, 17), (14, 18)]).has_run(19, 5));
+        assert!(!board(&[(1, 3), (2, 3), (3, 3), (4, 3), (6, 3)]).has_run(1, 5));
+        assert!(!board(&[(18, 15), (18, 16), (18, 17), (18, 18)]).has_run(20, 5));
+    }
+
+    #[test]
+    fn runs_dont_wrap_around_rows() {
+        assert!(!board(&[(16, 2), (17, 2), (18, 2), (0, 3), (1, 3)]).has_run(1, 5));
+        assert!(!board(&[(17, 0), (18, 1), (0, 3), (1, 4), (2, 5)]).has_run(21, 5));
+        assert!(!board(&[(1, 0), (0, 1), (18, 3), (17, 4), (16, 5)]).has_run(19, 5));
+        assert!(!board(&[(17, 17), (18, 17), (0, 18), (1, 18), (2, 18)]).has_run(1, 5));
+    }
+
+    #[test]
+    fn shifts_carry_bits_across_words() {
+        let last = board(&[(18, 18)]);
+        assert_eq!((last >> 377) << 377, last);
+        assert_eq!(board(&[(3, 3)]) << 1, board(&[(4, 3)]));
+        assert_eq!(board(&[(4, 3)]) >> 1, board(&[(3, 3)]));
+        assert!((last << 20).is_empty()); // past the last word
+    }
+}
\ No newline at end of file
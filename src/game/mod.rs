@@ -1,4 +1,6 @@
 pub mod tictactoe_game;
 pub mod ai;
 mod tictactoe_core;
+mod bitboard;
+mod symmetry;
 mod turn_logger;
@@ -0,0 +1,61 @@
+use crate::game::tictactoe_core::TicTacToe;
+
+fn transform(symmetry: usize, x: usize, y: usize, max_x: usize, max_y: usize) -> (usize, usize) {
+    match symmetry {
+        0 => (x, y),
+        1 => (max_x - x, y),
+        2 => (x, max_y - y),
+        3 => (max_x - x, max_y - y),
+        4 => (y, x),
+        5 => (max_y - y, max_x - x),
+        6 => (max_y - y, x),
+        _ => (y, max_x - x)
+    }
+}
+
+#[derive(Clone)]
+pub struct BoardSymmetries {
+    permutations: Vec<Vec<usize>>, // permutations[s][i] is where the square i goes under the symmetry s, identity first
+    inverses: Vec<Vec<usize>>
+}
+
+impl BoardSymmetries {
+    pub fn new(x_size: usize, y_size: usize) -> BoardSymmetries {
+        // only square boards can be rotated by 90 degrees or transposed
+        let amount = if x_size == y_size { 8 } else { 4 };
+        let mut permutations = vec![];
+        let mut inverses = vec![];
+        for symmetry in 0..amount {
+            let mut permutation = vec![0; x_size * y_size];
+            let mut inverse = vec![0; x_size * y_size];
+            for y in 0..y_size {
+                for x in 0..x_size {
+                    let (new_x, new_y) = transform(symmetry, x, y, x_size - 1, y_size - 1);
+                    let from = x + x_size * y;
+                    let to = new_x + x_size * new_y;
+                    permutation[from] = to;
+                    inverse[to] = from;
+                }
+            }
+            permutations.push(permutation);
+            inverses.push(inverse);
+        }
+        BoardSymmetries { permutations, inverses }
+    }
+
+    pub fn len(&self) -> usize {
+        self.permutations.len()
+    }
+
+    pub fn map_index(&self, symmetry: usize, index: usize) -> usize {
+        self.permutations[symmetry][index]
+    }
+
+    pub fn unmap_index(&self, symmetry: usize, index: usize) -> usize {
+        self.inverses[symmetry][index]
+    }
+
+    pub fn hashes(&self, board: &TicTacToe) -> Vec<u64> {
+        self.permutations.iter().map(|permutation| board.transformed_hash(permutation)).collect()
+    }
+}
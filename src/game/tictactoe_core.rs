@@ -1,4 +1,6 @@
 use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use crate::game::bitboard::{Bitboard, MAX_BITS};
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum SquareState {
@@ -10,8 +12,35 @@ pub enum SquareState {
 pub enum TurnState {
     Continue,
     Draw,
-    Error,
-    Victory
+    Victory(WinningLine)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MoveError {
+    Occupied(usize, usize), // (x, y) of the filled square
+    OutOfBounds(usize, usize),
+    GameFinished
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LineDirection {
+    Horizontal, // -
+    Vertical, // |
+    LeftDiagonal, // \
+    RightDiagonal // /
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct WinningLine {
+    pub direction: LineDirection,
+    pub squares: Vec<(usize, usize)> // (x, y) of every square in the line, from start to end
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThreatLine { // a line of one symbol that's still missing some squares to win
+    pub direction: LineDirection,
+    pub squares: Vec<(usize, usize)>, // (x, y) of the symbol's squares in the line, from start to end
+    pub empty_squares: Vec<(usize, usize)> // (x, y) of the squares that complete it
 }
 
 #[derive(Clone)]
@@ -25,6 +54,14 @@ pub struct TicTacToe {
     pub seq_to_win: usize,
     pub empty_space_symbol: char,
     pub filled: usize,
+    pub winning_line: Option<WinningLine>,
+    history: Vec<usize>, // indexes of the filled squares, in the order they were played
+    redo_stack: Vec<(usize, SquareState)>,
+    // bitboards are indexed by x + (x_size+1)*y, the extra empty column stops lines from wrapping around rows
+    valid: Bitboard,
+    occupied: Bitboard,
+    symbol_boards: Vec<(char, Bitboard)>,
+    hash: u64, // zobrist hash, xored with a square's key every time it gets filled or emptied
 }
 
 impl Display for TurnState {
@@ -32,22 +69,93 @@ impl Display for TurnState {
         return match self {
             TurnState::Continue => write!(f, "Continue"),
             TurnState::Draw => write!(f, "Draw"),
-            TurnState::Error => write!(f, "Error"),
-            TurnState::Victory => write!(f, "Victory"),
+            TurnState::Victory(_) => write!(f, "Victory"),
         }
     }
 }
 
+impl Display for MoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::Occupied(_, _) => write!(f, "That square is already filled!"),
+            MoveError::OutOfBounds(_, _) => write!(f, "That position is outside of the board!"),
+            MoveError::GameFinished => write!(f, "The game is already finished!"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl Display for LineDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineDirection::Horizontal => write!(f, "Horizontal"),
+            LineDirection::Vertical => write!(f, "Vertical"),
+            LineDirection::LeftDiagonal => write!(f, "Left Diagonal"),
+            LineDirection::RightDiagonal => write!(f, "Right Diagonal"),
+        }
+    }
+}
+
+impl LineDirection {
+    pub const ALL: [LineDirection; 4] = [LineDirection::Horizontal, LineDirection::Vertical, LineDirection::LeftDiagonal, LineDirection::RightDiagonal];
+
+    pub fn step(&self) -> (i32, i32) {
+        match self {
+            LineDirection::Horizontal => (1, 0),
+            LineDirection::Vertical => (0, 1),
+            LineDirection::LeftDiagonal => (1, 1),
+            LineDirection::RightDiagonal => (1, -1),
+        }
+    }
+}
+
+impl WinningLine {
+    pub fn start(&self) -> (usize, usize) {
+        self.squares[0]
+    }
+    pub fn end(&self) -> (usize, usize) {
+        self.squares[self.squares.len() - 1]
+    }
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        self.squares.contains(&(x, y))
+    }
+}
+
 impl PartialEq for TicTacToe {
     fn eq(&self, other: &Self) -> bool {
-        self.squares.eq(&other.squares)
+        self.hash == other.hash && self.squares.eq(&other.squares)
     }
 
     fn ne(&self, other: &Self) -> bool {
-        self.squares.ne(&other.squares)
+        self.hash != other.hash || self.squares.ne(&other.squares)
     }
 }
 
+impl Eq for TicTacToe {}
+
+impl Hash for TicTacToe {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash)
+    }
+}
+
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+fn zobrist_key(index: usize, symbol: char) -> u64 {
+    // derived from the square and symbol instead of a random table, so hashes are the same on every run
+    splitmix64(((index as u64) << 32) | symbol as u64)
+}
+
+fn empty_board_hash(x_size: usize, y_size: usize, seq_to_win: usize) -> u64 {
+    splitmix64(!(((x_size as u64) << 42) | ((y_size as u64) << 21) | seq_to_win as u64))
+}
+
 impl Default for TicTacToe {
     fn default() -> Self {
         TicTacToe {
@@ -59,7 +167,14 @@ impl Default for TicTacToe {
             squares: vec![SquareState::None; 9],
             seq_to_win: 3,
             empty_space_symbol: ' ',
-            filled: 0
+            filled: 0,
+            winning_line: None,
+            history: vec![],
+            redo_stack: vec![],
+            valid: TicTacToe::valid_squares_board(3, 3),
+            occupied: Bitboard::default(),
+            symbol_boards: vec![],
+            hash: empty_board_hash(3, 3, 3)
         }
     }
 }
@@ -71,53 +186,32 @@ pub trait SpecialBoardChecks {
     fn check_blocked_op_spaces(&self, index: usize, op_placed_square: SquareState) -> [usize; 4];
 }
 
-impl Display for TicTacToe {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        fn add_col_margin(s: &mut String, col_margin: usize){
-            for _ in 0..col_margin {
-                s.push_str(" ")
-            }
-        }
-        fn add_line_margin(s: &mut String, line_margin: usize){
-            for _ in 1..line_margin {
-                s.push_str(" ")
+pub trait ThreatAnalysis {
+    fn lines_one_move_from_winning(&self, symbol: char) -> Vec<ThreatLine>; // seq_to_win - 1 symbols and one empty square
+    fn open_lines(&self, symbol: char) -> Vec<ThreatLine>; // seq_to_win - 1 symbols in a row with both ends empty, one block can't stop them
+    fn fork_squares(&self, symbol: char) -> Vec<usize>; // moves that leave two winning squares or more
+    fn winning_squares_through(&self, index: usize, symbol: char) -> Vec<usize>; // with symbol on index, the squares that win on its lines
+    fn winning_square_count_through(&self, index: usize, symbol: char) -> usize; // the same, without building the list, for every move the Ai scores
+}
+
+impl TicTacToe {
+    // the board with some empty squares showing a mark instead, like "(1)"
+    pub fn render(&self, marks: &[((usize, usize), char)]) -> String {
+        fn add_margin(s: &mut String, margin: usize){
+            for _ in 0..margin {
+                s.push(' ')
             }
         }
         let mut s = String::new();
-        let mut lane = 1;
-        let mut col_margin = self.y_size.to_string().len()+4;
-        if col_margin % 2 == 0 {
-            col_margin += 1;
-        }
-        let mut line_margin = self.x_size.to_string().len();
-        let mut col = 1;
-        add_col_margin(&mut s, col_margin/2);
-        for i in 0..(self.x_size*3) {
-            if i % 3 == 0 {
-                //add_col_margin(&mut s, (col_margin/2)-1);
-                s.push_str(format!(" {}", col).as_str());
-                col += 1
-            }else {
-                //add_col_margin(&mut s, (col_margin/2)-1);
-                s.push_str(" ");
-            }
+        let label_width = self.y_size.to_string().len() + 1; // widest line label, like "10-"
+        add_margin(&mut s, label_width);
+        for col in 1..=self.x_size {
+            s.push_str(format!(" {:<3}", col).as_str());
         }
-        s.push_str(format!("\n{}-", lane).as_str());
-        add_line_margin(&mut s, line_margin);
-        for i in 0..self.squares.len() {
-            match self.squares.get(i).unwrap() {
-                SquareState::Filled(c) => {
-                    //add_col_margin(&mut s, col_margin/2);
-                    s.push_str(&*format!(" {} ", c));
-                }
-                SquareState::None => {
-                    //add_col_margin(&mut s, col_margin/2);
-                    s.push_str(&*format!(" {} ", self.empty_space_symbol));
-                }
-            }
-            if (i > 0) && ((i + 1) % self.x_size == 0) && !(lane == self.y_size) {
-                s.push_str("\n");
-                add_col_margin(&mut s, col_margin/2);
+        for lane in 1..=self.y_size {
+            if lane > 1 {
+                s.push('\n');
+                add_margin(&mut s, label_width);
                 for i in 0..(self.x_size*3)+self.x_size-1 {
                     if (i + 1) % 4 == 0 {
                         s.push(self.cross_spacer)
@@ -125,17 +219,36 @@ impl Display for TicTacToe {
                         s.push(self.horizontal_spacer)
                     }
                 }
-                lane += 1;
-                s.push_str(format!("\n{}-", lane).as_str());
-                add_line_margin(&mut s, line_margin);
-                if (lane+1).to_string().len() > lane.to_string().len() {
-                    line_margin -= 1
+            }
+            s.push_str(format!("\n{:<width$}", format!("{}-", lane), width = label_width).as_str());
+            for x in 0..self.x_size {
+                match self.get_square(x, lane - 1).unwrap() {
+                    SquareState::Filled(c) => {
+                        if self.winning_line.as_ref().is_some_and(|line| line.contains(x, lane - 1)) {
+                            s.push_str(&format!("[{}]", c));
+                        }else {
+                            s.push_str(&format!(" {} ", c));
+                        }
+                    }
+                    SquareState::None => {
+                        match marks.iter().find(|(coord, _)| *coord == (x, lane - 1)) {
+                            Some((_, mark)) => s.push_str(&format!("({})", mark)),
+                            None => s.push_str(&format!(" {} ", self.empty_space_symbol))
+                        }
+                    }
+                }
+                if x < self.x_size - 1 {
+                    s.push(self.vertical_spacer)
                 }
-            }else if i < self.squares.len() - 1 {
-                s.push(self.vertical_spacer)
             }
         }
-        write!(f, "{}\n", s)
+        s
+    }
+}
+
+impl Display for TicTacToe {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.render(&[]))
     }
 }
 
@@ -233,17 +346,240 @@ impl SpecialBoardChecks for TicTacToe {
     }
 }
 
+impl ThreatAnalysis for TicTacToe {
+    fn lines_one_move_from_winning(&self, symbol: char) -> Vec<ThreatLine> {
+        let mut lines = vec![];
+        for i in 0..self.size() {
+            for direction in LineDirection::ALL {
+                if let Some(line) = self.line_from(i, direction, self.seq_to_win) {
+                    if self.symbols_in_line(&line, symbol, None) == Some(self.seq_to_win - 1) {
+                        lines.push(self.threat_line(direction, &line, symbol))
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn open_lines(&self, symbol: char) -> Vec<ThreatLine> {
+        let mut lines = vec![];
+        if self.seq_to_win < 2 {
+            return lines
+        }
+        for i in 0..self.size() {
+            for direction in LineDirection::ALL {
+                if let Some(line) = self.line_from(i, direction, self.seq_to_win + 1) { // the ends and the symbols between them
+                    let (ends, middle) = ([line[0], line[self.seq_to_win]], &line[1..self.seq_to_win]);
+                    if ends.iter().all(|end| self.squares[*end] == SquareState::None) &&
+                        middle.iter().all(|square| self.squares[*square] == SquareState::Filled(symbol)) {
+                        lines.push(self.threat_line(direction, &line, symbol))
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn fork_squares(&self, symbol: char) -> Vec<usize> {
+        (0..self.size()).filter(|i| self.squares[*i] == SquareState::None).filter(|i| {
+            let wins_already = self.lines_through(*i).iter().any(|line| self.symbols_in_line(line, symbol, Some(*i)) == Some(self.seq_to_win));
+            !wins_already && self.winning_squares_through(*i, symbol).len() >= 2 // a move that already wins isn't a fork
+        }).collect()
+    }
+
+    fn winning_squares_through(&self, index: usize, symbol: char) -> Vec<usize> {
+        let mut squares = vec![];
+        self.for_each_winning_square_through(index, symbol, |square| squares.push(square));
+        squares.sort_unstable();
+        squares
+    }
+
+    fn winning_square_count_through(&self, index: usize, symbol: char) -> usize {
+        let mut count = 0;
+        self.for_each_winning_square_through(index, symbol, |_| count += 1);
+        count
+    }
+}
+
+impl TicTacToe {
+    // indexes of the length squares going from index in the direction, None if they don't fit in the board
+    fn line_from(&self, index: usize, direction: LineDirection, length: usize) -> Option<Vec<usize>> {
+        let (x, y) = self.get_index_coord(index);
+        let (step_x, step_y) = direction.step();
+        let (end_x, end_y) = (x as i32 + step_x * (length as i32 - 1), y as i32 + step_y * (length as i32 - 1));
+        if length == 0 || end_x >= self.x_size as i32 || end_y < 0 || end_y >= self.y_size as i32 {
+            return None
+        }
+        Some((0..length as i32).map(|n| self.get_coord_index((x as i32 + step_x * n) as usize, (y as i32 + step_y * n) as usize)).collect())
+    }
+
+    // every empty square that wins with symbol on index and on itself, once, going along the lines through index square by square
+    fn for_each_winning_square_through(&self, index: usize, symbol: char, mut found: impl FnMut(usize)) {
+        let (x, y) = self.get_index_coord(index);
+        let seq = self.seq_to_win as i32;
+        for direction in LineDirection::ALL {
+            let (step_x, step_y) = direction.step();
+            let square = |n: i32| { // n squares away from index in the direction, None outside the board
+                let (square_x, square_y) = (x as i32 + step_x * n, y as i32 + step_y * n);
+                let inside = square_x >= 0 && square_y >= 0 && square_x < self.x_size as i32 && square_y < self.y_size as i32;
+                inside.then(|| self.get_coord_index(square_x as usize, square_y as usize))
+            };
+            for n in (1 - seq)..seq {
+                let Some(empty) = square(n).filter(|i| n != 0 && self.squares[*i] == SquareState::None) else {
+                    continue
+                };
+                // a line with both squares in it, and symbol on every other square
+                let wins = ((n.max(0) + 1 - seq)..=n.min(0)).any(|start| (start..start + seq).all(|m| {
+                    m == 0 || m == n || square(m).is_some_and(|i| self.squares[i] == SquareState::Filled(symbol))
+                }));
+                if wins {
+                    found(empty)
+                }
+            }
+        }
+    }
+
+    // every line of seq_to_win squares that goes through index
+    fn lines_through(&self, index: usize) -> Vec<Vec<usize>> {
+        let (x, y) = self.get_index_coord(index);
+        let mut lines = vec![];
+        for direction in LineDirection::ALL {
+            let (step_x, step_y) = direction.step();
+            for n in 0..self.seq_to_win as i32 { // the line that has index as its nth square
+                let (start_x, start_y) = (x as i32 - step_x * n, y as i32 - step_y * n);
+                if start_x < 0 || start_y < 0 || start_y >= self.y_size as i32 {
+                    continue
+                }
+                lines.extend(self.line_from(self.get_coord_index(start_x as usize, start_y as usize), direction, self.seq_to_win));
+            }
+        }
+        lines
+    }
+
+    // amount of symbols in the line counting assumed as one of them, None if the line has another symbol
+    fn symbols_in_line(&self, line: &[usize], symbol: char, assumed: Option<usize>) -> Option<usize> {
+        let mut count = 0;
+        for square in line {
+            match self.squares[*square] {
+                _ if assumed == Some(*square) => count += 1,
+                SquareState::Filled(c) if c == symbol => count += 1,
+                SquareState::Filled(_) => return None,
+                SquareState::None => {}
+            }
+        }
+        Some(count)
+    }
+
+    fn threat_line(&self, direction: LineDirection, line: &[usize], symbol: char) -> ThreatLine {
+        let coords = |state: SquareState| line.iter().filter(|square| self.squares[**square] == state).map(|square| self.get_index_coord(*square)).collect();
+        ThreatLine { direction, squares: coords(SquareState::Filled(symbol)), empty_squares: coords(SquareState::None) }
+    }
+}
+
 impl TicTacToe {
     pub fn new(x_size: usize, y_size: usize, seq_to_win: usize, empty_space_symbol: char) -> TicTacToe {
+        assert!(TicTacToe::fits_bitboard(x_size, y_size), "Board of {}x{} is too big!", x_size, y_size);
         let mut t = TicTacToe::default();
         t.empty_space_symbol = empty_space_symbol;
         t.y_size = y_size;
         t.x_size = x_size;
         t.seq_to_win = seq_to_win;
         t.squares = vec![SquareState::None; x_size*y_size];
+        t.valid = TicTacToe::valid_squares_board(x_size, y_size);
+        t.hash = empty_board_hash(x_size, y_size, seq_to_win);
         t
     }
 
+    pub fn position_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn transformed_hash(&self, permutation: &[usize]) -> u64 { // hash of the board with every square i moved to permutation[i]
+        let mut hash = empty_board_hash(self.x_size, self.y_size, self.seq_to_win);
+        for i in self.history.iter() {
+            if let SquareState::Filled(c) = self.squares[*i] {
+                hash ^= zobrist_key(permutation[*i], c)
+            }
+        }
+        hash
+    }
+
+    // empty squares at most radius squares away from a filled one in any direction, or the center on an empty board
+    pub fn candidate_moves(&self, radius: usize) -> Vec<usize> {
+        if self.filled == 0 { // the squares within the radius of the center, the center first
+            let (cx, cy) = (self.x_size / 2, self.y_size / 2);
+            let center = cx + self.x_size * cy;
+            return std::iter::once(center).chain((0..self.size()).filter(|i| {
+                let (x, y) = self.get_index_coord(*i);
+                *i != center && x.abs_diff(cx) <= radius && y.abs_diff(cy) <= radius
+            })).collect()
+        }
+        let row_width = self.x_size + 1;
+        let mut near = self.occupied;
+        for _ in 0..radius {
+            let mut grown = near;
+            for shift in [1, row_width, row_width + 1, row_width - 1] {
+                grown = grown | (near >> shift) | (near << shift);
+            }
+            near = grown & self.valid; // the padding column would let it wrap into the next row
+        }
+        let candidates = near & !self.occupied;
+        (0..self.size()).filter(|i| {
+            let (x, y) = self.get_index_coord(*i);
+            candidates.get(self.bit_index(x, y))
+        }).collect()
+    }
+
+    pub fn fits_bitboard(x_size: usize, y_size: usize) -> bool { // true for every board up to 19x19
+        (x_size + 1) * y_size <= MAX_BITS
+    }
+
+    fn valid_squares_board(x_size: usize, y_size: usize) -> Bitboard {
+        let mut board = Bitboard::default();
+        for y in 0..y_size {
+            for x in 0..x_size {
+                board.set(x + (x_size + 1) * y)
+            }
+        }
+        board
+    }
+
+    fn bit_index(&self, x: usize, y: usize) -> usize {
+        x + (self.x_size + 1) * y
+    }
+
+    fn state_board(&self, state: &SquareState) -> Bitboard {
+        match state {
+            SquareState::Filled(c) => {
+                self.symbol_boards.iter().find(|(symbol, _)| symbol == c).map_or(Bitboard::default(), |(_, board)| *board)
+            }
+            SquareState::None => self.valid & !self.occupied
+        }
+    }
+
+    fn toggle_bit(&mut self, i: usize, state: SquareState) {
+        let coord = self.get_index_coord(i);
+        let bit = self.bit_index(coord.0, coord.1);
+        if let SquareState::Filled(c) = state {
+            self.hash ^= zobrist_key(i, c);
+            let place = !self.occupied.get(bit);
+            let board = match self.symbol_boards.iter_mut().find(|(symbol, _)| *symbol == c) {
+                Some((_, board)) => board,
+                None => {
+                    self.symbol_boards.push((c, Bitboard::default()));
+                    &mut self.symbol_boards.last_mut().unwrap().1
+                }
+            };
+            if place {
+                board.set(bit);
+                self.occupied.set(bit)
+            }else {
+                board.unset(bit);
+                self.occupied.unset(bit)
+            }
+        }
+    }
+
     pub fn get_square(&self, x: usize, y: usize) -> Option<&SquareState> {
         if x >= self.x_size || y >= self.y_size {
             return None
@@ -261,33 +597,72 @@ impl TicTacToe {
         (x, y)
     }
 
-    pub fn set_square(&mut self, x: usize, y: usize, state: SquareState) -> TurnState {
-        let i = self.get_coord_index(x, y);
-        if self.squares[i] != SquareState::None {
-            return TurnState::Error
+    pub fn set_square(&mut self, x: usize, y: usize, state: SquareState) -> Result<TurnState, MoveError> {
+        if x >= self.x_size || y >= self.y_size {
+            return Err(MoveError::OutOfBounds(x, y))
         }
-        self.squares[i] = state;
-        self.filled += 1;
-        self.check_game_over(x, y, state)
+        let i = self.get_coord_index(x, y);
+        self.set_square_from_index(i, state)
     }
 
-    pub fn set_square_from_index(&mut self, i: usize, state: SquareState) -> TurnState {
+    pub fn set_square_from_index(&mut self, i: usize, state: SquareState) -> Result<TurnState, MoveError> {
+        let coord = self.get_index_coord(i);
+        if i >= self.squares.len() {
+            return Err(MoveError::OutOfBounds(coord.0, coord.1))
+        }
+        if self.is_finished() {
+            return Err(MoveError::GameFinished)
+        }
         if self.squares[i] != SquareState::None {
-            return TurnState::Error
+            return Err(MoveError::Occupied(coord.0, coord.1))
         }
+        self.redo_stack.clear();
+        Ok(self.place(i, state))
+    }
+
+    fn place(&mut self, i: usize, state: SquareState) -> TurnState {
+        let coord = self.get_index_coord(i);
         self.squares[i] = state;
+        self.toggle_bit(i, state);
         self.filled += 1;
-        let coord = self.get_index_coord(i);
+        self.history.push(i);
         self.check_game_over(coord.0, coord.1, state)
     }
 
+    pub fn undo(&mut self) -> Option<(usize, SquareState)> { // returns the index and state of the removed move
+        let i = self.history.pop()?;
+        let state = self.squares[i];
+        self.squares[i] = SquareState::None;
+        self.toggle_bit(i, state);
+        self.filled -= 1;
+        self.winning_line = None;
+        self.redo_stack.push((i, state));
+        Some((i, state))
+    }
+
+    pub fn redo(&mut self) -> Option<TurnState> {
+        let (i, state) = self.redo_stack.pop()?;
+        Some(self.place(i, state))
+    }
+
+    pub fn history(&self) -> &[usize] {
+        &self.history
+    }
+
+    pub fn redoable_moves(&self) -> usize {
+        self.redo_stack.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.winning_line.is_some() || self.check_draw()
+    }
+
     fn check_game_over(&mut self, x: usize, y: usize, state: SquareState) -> TurnState {
-        if self.filled >= self.seq_to_win + 2 &&
-            ((self.check_x(x, y, state, true, false) >= self.seq_to_win) ||
-                (self.check_y(x, y, state, true, false) == self.seq_to_win) ||
-                (self.check_left_diag(x, y, state, true, false) == self.seq_to_win) ||
-                (self.check_right_diag(x, y, state, true, false) == self.seq_to_win))  {
-            return TurnState::Victory
+        if self.filled >= self.seq_to_win && self.has_sequence(&state) {
+            if let Some(line) = self.find_winning_line(x, y, state) {
+                self.winning_line = Some(line.clone());
+                return TurnState::Victory(line)
+            }
         }
         if self.check_draw() {
             return TurnState::Draw
@@ -295,50 +670,66 @@ impl TicTacToe {
         return TurnState::Continue
     }
 
+    fn has_sequence(&self, state: &SquareState) -> bool {
+        let board = self.state_board(state);
+        let row_width = self.x_size + 1;
+        [1, row_width, row_width + 1, row_width - 1].iter().any(|shift| board.has_run(*shift, self.seq_to_win))
+    }
+
+    fn find_winning_line(&self, x: usize, y: usize, state: SquareState) -> Option<WinningLine> {
+        for direction in LineDirection::ALL {
+            let (step_x, step_y) = direction.step();
+            let walk = |sign: i32| {
+                let mut squares = vec![];
+                let mut check_x = x as i32 + step_x * sign;
+                let mut check_y = y as i32 + step_y * sign;
+                while check_x >= 0 && check_y >= 0 && self.get_square(check_x as usize, check_y as usize) == Some(&state) {
+                    squares.push((check_x as usize, check_y as usize));
+                    check_x += step_x * sign;
+                    check_y += step_y * sign;
+                }
+                squares
+            };
+            let mut squares = walk(-1);
+            squares.reverse();
+            squares.push((x, y));
+            squares.append(&mut walk(1));
+            if squares.len() >= self.seq_to_win {
+                return Some(WinningLine { direction, squares })
+            }
+        }
+        None
+    }
+
     fn all_lines_checker(&self, x: usize, y: usize, state: &SquareState, stop_counting: bool, check_x_axis: bool, check_y_axis: bool, inverted: bool) -> (usize, usize) {
         let mut available_spaces_count = 1;
         let mut seq_count = 1;
-        let mut dist;
-        let loop_condition: Box<dyn Fn(i32, (&mut usize, &mut usize)) -> bool> = if check_x_axis == check_y_axis {
-            Box::new(|dist, (check_x, check_y)| {
-                let (offset_x, offset_y) = if inverted { (dist, -dist) } else { (dist, dist) };
-                let result = x as i32 + offset_x >= 0 && y as i32 + offset_y >= 0 && dist < self.seq_to_win as i32;
-                if result {
-                    *check_y = (y as i32 + offset_y) as usize;
-                    *check_x = (x as i32 + offset_x) as usize;
-                }
-                result
-            })
-        } else {
-            Box::new(|dist, (check_x, check_y)| {
-                let (value, to_check) = if check_x_axis {
-                    (x, check_x)
-                } else {
-                    (y, check_y)
-                };
-                let result = value as i32 + dist >= 0 && dist < self.seq_to_win as i32;
-                if result {
-                    *to_check = (value as i32 + dist) as usize;
-                }
-                result
-            })
+        let row_width = (self.x_size + 1) as i32;
+        let step = match (check_x_axis, check_y_axis) {
+            (true, false) => 1,
+            (false, true) => row_width,
+            _ if inverted => 1 - row_width,
+            _ => row_width + 1
         };
-        for i in [1, -1].iter().copied() {
-            dist = i;
-            let mut checking_x = x;
-            let mut checking_y = y;
-            while loop_condition(dist, (&mut checking_x, &mut checking_y)) {
-                let square_state = self.get_square(checking_x, checking_y);
-                if let Some(n) = square_state {
-                    if n == state {
-                        seq_count += 1;
-                    }else if stop_counting || n != &SquareState::None {
-                        break
-                    }
-                }else {
+        let own = self.state_board(state);
+        let blocking = self.occupied & !own;
+        let last_bit = row_width * self.y_size as i32;
+        for i in [1, -1] {
+            // only the forward walk is capped by the sequence length
+            let max_dist = if i == 1 { self.seq_to_win as i32 - 1 } else { i32::MAX };
+            let mut bit = self.bit_index(x, y) as i32;
+            let mut dist = 0;
+            while dist < max_dist {
+                bit += step * i;
+                if bit < 0 || bit >= last_bit || !self.valid.get(bit as usize) {
+                    break
+                }
+                if own.get(bit as usize) {
+                    seq_count += 1;
+                }else if stop_counting || blocking.get(bit as usize) {
                     break
                 }
-                dist += i;
+                dist += 1;
                 available_spaces_count += 1;
             }
         }
@@ -383,6 +774,175 @@ impl TicTacToe {
 
     pub fn clear(&mut self) {
         self.squares = vec![SquareState::None; self.x_size*self.y_size];
-        self.filled = 0
+        self.filled = 0;
+        self.winning_line = None;
+        self.history.clear();
+        self.redo_stack.clear();
+        self.occupied = Bitboard::default();
+        self.symbol_boards.clear();
+        self.hash = empty_board_hash(self.x_size, self.y_size, self.seq_to_win)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    fn board(x_size: usize, y_size: usize, seq_to_win: usize, xs: &[(usize, usize)], os: &[(usize, usize)]) -> TicTacToe {
+        let mut board = TicTacToe::new(x_size, y_size, seq_to_win, ' ');
+        for (x, y) in xs {
+            board.set_square(*x, *y, SquareState::Filled('X')).unwrap();
+        }
+        for (x, y) in os {
+            board.set_square(*x, *y, SquareState::Filled('O')).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn lines_one_move_from_winning_lists_every_window() {
+        // _XXXX_ on row 4, and a broken X_XX on column 8 blocked by nothing
+        let board = board(9, 9, 5, &[(2, 4), (3, 4), (4, 4), (5, 4), (8, 0), (8, 2), (8, 3)], &[(0, 0)]);
+        let lines = board.lines_one_move_from_winning('X');
+        let mut empty: Vec<(usize, usize)> = lines.iter().flat_map(|line| line.empty_squares.clone()).collect();
+        empty.sort_unstable();
+        assert_eq!(empty, vec![(1, 4), (6, 4)]);
+        assert!(lines.iter().all(|line| line.direction == LineDirection::Horizontal && line.squares.len() == 4));
+        assert_eq!(board.open_lines('X').len(), 1);
+        assert!(board.lines_one_move_from_winning('O').is_empty());
+    }
+
+    #[test]
+    fn a_blocked_line_is_not_one_move_from_winning() {
+        let board = board(9, 9, 5, &[(2, 4), (3, 4), (4, 4), (5, 4)], &[(1, 4), (6, 4)]);
+        assert!(board.lines_one_move_from_winning('X').is_empty());
+        assert!(board.open_lines('X').is_empty());
+    }
+
+    #[test]
+    fn fork_squares_leave_two_winning_squares() {
+        // X on opposite corners of a 3x3 board with O in the middle, either free corner makes two lines of two
+        let board = board(3, 3, 3, &[(0, 0), (2, 2)], &[(1, 1)]);
+        let forks: Vec<(usize, usize)> = board.fork_squares('X').into_iter().map(|i| board.get_index_coord(i)).collect();
+        assert_eq!(forks, vec![(2, 0), (0, 2)]);
+        assert!(board.fork_squares('O').is_empty());
+    }
+
+    #[test]
+    fn a_winning_move_is_not_a_fork() {
+        // (2, 0) wins on the top row and would also leave two winning squares on its column and diagonal
+        let board = board(4, 4, 3, &[(0, 0), (1, 0), (1, 1), (2, 1)], &[(3, 3), (0, 3), (3, 1), (1, 3)]);
+        let top_right = board.get_coord_index(2, 0);
+        assert!(board.winning_squares_through(top_right, 'X').len() >= 2);
+        assert!(!board.fork_squares('X').contains(&top_right));
+    }
+
+    #[test]
+    fn lines_across_the_end_of_a_row_dont_win() {
+        let mut board = board(19, 19, 5, &[(16, 2), (17, 2), (18, 2), (0, 3)], &[]);
+        assert!(board.set_square(1, 3, SquareState::Filled('X')) == Ok(TurnState::Continue));
+        assert!(board.set_square(18, 18, SquareState::Filled('O')) == Ok(TurnState::Continue));
+        for y in 14..18 {
+            board.set_square(18, y, SquareState::Filled('X')).unwrap();
+        }
+        assert!(matches!(board.set_square(18, 13, SquareState::Filled('X')), Ok(TurnState::Victory(_))));
+    }
+
+    fn hash_from_scratch(board: &TicTacToe) -> u64 { // the same squares filled on a new board, in index order
+        let mut scratch = TicTacToe::new(board.x_size, board.y_size, board.seq_to_win, ' ');
+        for (i, square) in board.squares.iter().enumerate() {
+            if *square != SquareState::None {
+                scratch.set_square_from_index(i, *square).unwrap();
+            }
+        }
+        scratch.position_hash()
+    }
+
+    #[test]
+    fn the_hash_matches_a_new_board_after_undo_and_redo() {
+        let mut board = TicTacToe::new(5, 4, 4, ' ');
+        let empty = board.position_hash();
+        for (n, i) in [7, 12, 0, 19, 3, 8].into_iter().enumerate() {
+            let symbol = if n % 2 == 0 { 'X' } else { 'O' };
+            board.set_square_from_index(i, SquareState::Filled(symbol)).unwrap();
+            assert_eq!(board.position_hash(), hash_from_scratch(&board));
+        }
+        let full = board.position_hash();
+        for _ in 0..3 {
+            board.undo();
+            assert_eq!(board.position_hash(), hash_from_scratch(&board));
+        }
+        board.redo();
+        board.redo();
+        assert_eq!(board.position_hash(), hash_from_scratch(&board));
+        board.redo();
+        assert_eq!(board.position_hash(), full);
+        while board.undo().is_some() {}
+        assert_eq!(board.position_hash(), empty);
+        let identity: Vec<usize> = (0..board.size()).collect();
+        board.set_square_from_index(12, SquareState::Filled('X')).unwrap();
+        assert_eq!(board.position_hash(), board.transformed_hash(&identity));
+        assert_ne!(board.position_hash(), empty);
+    }
+
+    #[test]
+    fn undo_and_redo_replay_the_moves() {
+        let mut board = board(3, 3, 3, &[(0, 0), (1, 0)], &[(0, 1), (1, 1)]);
+        assert!(matches!(board.set_square(2, 0, SquareState::Filled('X')), Ok(TurnState::Victory(_))));
+        assert!(board.is_finished());
+        assert!(board.undo() == Some((2, SquareState::Filled('X'))));
+        assert!(board.winning_line.is_none() && !board.is_finished());
+        assert!(board.undo() == Some((4, SquareState::Filled('O'))));
+        assert_eq!((board.filled, board.redoable_moves()), (3, 2));
+        assert!(board.redo() == Some(TurnState::Continue));
+        assert!(matches!(board.redo(), Some(TurnState::Victory(_))));
+        assert!(board.redo().is_none());
+        board.undo();
+        board.set_square(2, 2, SquareState::Filled('X')).unwrap(); // a new move drops the moves that were taken back
+        assert_eq!(board.redoable_moves(), 0);
+        assert_eq!(board.history(), &[0, 1, 3, 4, 8]);
+        while board.undo().is_some() {}
+        assert_eq!(board.filled, 0);
+        assert!(board.squares.iter().all(|square| *square == SquareState::None));
+    }
+
+    #[test]
+    fn winning_squares_match_the_lines_through_the_move() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (x_size, y_size, seq_to_win) in [(3, 3, 3), (7, 5, 4), (9, 9, 5)] {
+            let mut board = TicTacToe::new(x_size, y_size, seq_to_win, ' ');
+            for n in 0..x_size * y_size / 2 {
+                let empty: Vec<usize> = (0..board.size()).filter(|i| board.squares[*i] == SquareState::None).collect();
+                for index in empty.iter().copied() {
+                    for symbol in ['X', 'O'] {
+                        let mut expected: Vec<usize> = board.lines_through(index).into_iter()
+                            .filter(|line| board.symbols_in_line(line, symbol, Some(index)) == Some(seq_to_win - 1))
+                            .flat_map(|line| line.into_iter().filter(|square| *square != index && board.squares[*square] == SquareState::None))
+                            .collect();
+                        expected.sort_unstable();
+                        expected.dedup();
+                        assert_eq!(board.winning_squares_through(index, symbol), expected, "{}", board);
+                        assert_eq!(board.winning_square_count_through(index, symbol), expected.len());
+                    }
+                }
+                let symbol = if n % 2 == 0 { 'X' } else { 'O' };
+                if board.set_square_from_index(*empty.choose(&mut rng).unwrap(), SquareState::Filled(symbol)).unwrap() != TurnState::Continue {
+                    break
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_boards_give_the_squares_around_the_center() {
+        let board = TicTacToe::new(5, 4, 3, ' ');
+        assert_eq!(board.candidate_moves(1), vec![12, 6, 7, 8, 11, 13, 16, 17, 18]);
+        assert_eq!(board.candidate_moves(2)[0], 12);
+        assert_eq!(board.candidate_moves(2).len(), 20);
+        let board = TicTacToe::new(1, 1, 1, ' ');
+        assert_eq!(board.candidate_moves(2), vec![0]);
+    }
+}
\ No newline at end of file
@@ -1,10 +1,18 @@
 use std::fmt::{Display, Formatter};
 use std::io::{stdin, stdout, Write};
 use std::string::ToString;
+use std::sync::Arc;
+use std::thread;
 use std::thread::sleep;
 use std::time::Duration;
-use crate::game::ai::tictactoe_ai_player::Ai;
-use crate::game::tictactoe_core::{SquareState, TicTacToe, TurnState};
+use rand::{thread_rng, Rng};
+use crate::game::ai::heuristic_weights::HeuristicWeights;
+use crate::game::ai::mcts_player::{MctsPlayer, DEFAULT_ITERATIONS};
+use crate::game::ai::opening_book::OpeningBook;
+use crate::game::ai::tablebase::Tablebase;
+use crate::game::ai::threat_space::DEFAULT_THREAT_DEPTH;
+use crate::game::ai::tictactoe_ai_player::{Ai, DEFAULT_SEED};
+use crate::game::tictactoe_core::{MoveError, SquareState, ThreatAnalysis, TicTacToe, TurnState};
 use crate::game::turn_logger::TurnLogger;
 
 pub struct TicTacToeGame {
@@ -12,13 +20,17 @@ pub struct TicTacToeGame {
     player1: Player,
     player2: Player,
     game_state: GameState,
-    pub show_turn_info_mode: bool
+    pub show_turn_info_mode: bool,
+    pub compare_move_ordering: bool, // shown in debug mode, every Ai move gets searched a second time without move ordering
+    pub ai_time_budget: Option<Duration>,
+    pub seed: Option<u64> // None picks a new seed every game
 }
 
 #[derive(Clone)]
 pub enum PlayerType {
     Human,
-    Computer(Ai)
+    Computer(Box<Ai>), // boxed, so a player stays small no matter how much the Ai keeps
+    Mcts(Box<MctsPlayer>)
 }
 
 #[derive(Clone)]
@@ -29,7 +41,7 @@ pub struct Player {
 }
 
 enum GameState {
-    Player(usize, Player), // number, player info
+    Player(usize), // number of the player moving, the player itself stays in the game so its Ai keeps what it learned
     Finished,
     Begin(usize)
 }
@@ -37,15 +49,16 @@ enum GameState {
 pub enum AiDifficulties {
     Easy,
     Medium,
-    Hard
+    Hard,
+    Perfect // exact solver on small boards, plays like Hard on the bigger ones
 }
 
 impl Display for TicTacToeGame {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let state = &self.game_state;
         return match state {
-            GameState::Player(_, s) => {
-                write!(f, "\n{}'s turn:\n\n", s.name).and(self.board.fmt(f))
+            GameState::Player(n) => {
+                write!(f, "\n{}'s turn:\n\n", self.player(*n).name).and(self.board.fmt(f))
             }
             GameState::Finished => {
                 write!(f, "Game is finished!\n\n").and(self.board.fmt(f))
@@ -67,47 +80,64 @@ const TITLE: &str = "
 const AUTHOR: &str = "KnightLeo";
 const REPO_LINK: &str = "https://github.com/TrabalhosPUCPR/Rust-TicTacToe";
 const AI_MOVE_DELAY: u64 = 1000; // in ms
+const HINT_AMOUNT: usize = 3;
 
 impl TicTacToeGame {
     pub fn start_new_game_prompts() {
         loop {
-            println!("{}\t\tMade by {}\nRepo link: {}\n\nPlease choose an option:\n1-Load 1 player game\n2-Load 2 player game\n3-Load Ai game\n(default: 1)\n", TITLE, AUTHOR, REPO_LINK);
+            println!("{}\t\tMade by {}\nRepo link: {}\n\nPlease choose an option:\n1-Load 1 player game\n2-Load 2 player game\n3-Load Ai game\n4-Load Ai vs Monte Carlo Ai game\n(default: 1)\n", TITLE, AUTHOR, REPO_LINK);
             let mut game;
             let n = TicTacToeGame::input_usize();
             if n.is_none() || n.unwrap() == 1 {
-                println!("\nSelect a difficulty for the AI\n1-Easy\n2-Medium\n3-Hard\n(default: 3)\n");
-                let mut difficulty = AiDifficulties::Hard;
-                match TicTacToeGame::input_usize() {
-                    None => {}
-                    Some(n) => {
-                        match n {
-                            1 => difficulty = AiDifficulties::Easy,
-                            2 => difficulty = AiDifficulties::Medium,
-                            _ => {}
+                println!("\nSelect the opponent\n1-Ai\n2-Monte Carlo Ai\n(default: 1)\n");
+                if TicTacToeGame::input_usize() == Some(2) {
+                    game = TicTacToeGame::load_default_1player_mcts_game()
+                }else {
+                    println!("\nSelect a difficulty for the AI\n1-Easy\n2-Medium\n3-Hard\n4-Perfect (boards up to 4x4)\n(default: 3)\n");
+                    let mut difficulty = AiDifficulties::Hard;
+                    match TicTacToeGame::input_usize() {
+                        None => {}
+                        Some(n) => {
+                            match n {
+                                1 => difficulty = AiDifficulties::Easy,
+                                2 => difficulty = AiDifficulties::Medium,
+                                4 => difficulty = AiDifficulties::Perfect,
+                                _ => {}
+                            }
                         }
                     }
+                    game = TicTacToeGame::load_default_1player_game(difficulty)
                 }
-                game = TicTacToeGame::load_default_1player_game(difficulty)
             }else if n.unwrap() == 2 {
                 game = TicTacToeGame::load_default_2player_game()
             }else if n.unwrap() == 3 {
                 game = TicTacToeGame::load_default_ai_game(AiDifficulties::Hard, AiDifficulties::Hard)
+            }else if n == Some(4) {
+                game = TicTacToeGame::load_default_ai_vs_mcts_game(AiDifficulties::Hard)
             }else {
                 println!("Type a valid number!");
                 continue
             }
-            println!("Board Size: {}\nSequence to win: {}\nPlayer 1 symbol: {}\nPlayer 2 symbol: {}", game.board.size(), game.board.seq_to_win, game.player1.square_symbol, game.player2.square_symbol);
+            println!("Board Size: {}x{}\nSequence to win: {}\nPlayer 1 symbol: {}\nPlayer 2 symbol: {}", game.board.x_size, game.board.y_size, game.board.seq_to_win, game.player1.square_symbol, game.player2.square_symbol);
             println!("\n1-Start Game\n2-Configure Game\n(default: 1)");
             let result = TicTacToeGame::input_usize();
             if result.is_some() && result.unwrap() == 2 {
                 loop {
-                    println!("\n1-Change Board Size\n2-Debug Mode\nEnter: Start Game");
+                    println!("\n1-Change Board Size\n2-Debug Mode\n3-Ai Memory\n4-Ai Time Limit\n5-Ai Threads\n6-Ai Heuristic Weights\n7-Random Seed\n8-Ai Move Radius\n9-Ai Opening Book\n10-Ai Tablebase\n11-Monte Carlo Ai Limits\n12-Ai Move Ordering Comparison\nEnter: Start Game");
                     if let Some(n) = TicTacToeGame::input_usize() {
                         if n == 1 {
-                            println!("\nType board size:");
-                            let size;
+                            println!("\nType board width (number of columns):");
+                            let x_size;
                             if let Some(n) = TicTacToeGame::input_usize() {
-                                size = n;
+                                x_size = n;
+                            }else {
+                                println!("Invalid Input");
+                                continue
+                            }
+                            println!("\nType board height (number of lines):");
+                            let y_size;
+                            if let Some(n) = TicTacToeGame::input_usize() {
+                                y_size = n;
                             }else {
                                 println!("Invalid Input");
                                 continue
@@ -120,15 +150,146 @@ impl TicTacToeGame {
                                 println!("Invalid Input");
                                 continue
                             }
-                            if !game.change_size(size, length) {
+                            if !game.change_size(x_size, y_size, length) {
                                 println!("Board size and sequence to win are not valid");
                                 continue
                             }
                         } else if n == 2 {
                             println!("\nWould you like to activate debug mode? (Y/n)\n");
                             game.show_turn_info_mode = TicTacToeGame::input_bool();
+                        } else if n == 3 {
+                            println!("\nType the memory each Ai can use to remember positions, in MB:");
+                            if let Some(n) = TicTacToeGame::input_usize() {
+                                game.set_ai_memory(n * 1024 * 1024);
+                            }else {
+                                println!("Invalid Input");
+                                continue
+                            }
+                        } else if n == 4 {
+                            println!("\nType how long each Ai move can take, in ms (0: no limit):");
+                            match TicTacToeGame::input_usize() {
+                                Some(0) => game.ai_time_budget = None,
+                                Some(n) => game.ai_time_budget = Some(Duration::from_millis(n as u64)),
+                                None => {
+                                    println!("Invalid Input");
+                                    continue
+                                }
+                            }
+                        } else if n == 5 {
+                            let cores = thread::available_parallelism().map_or(1, |n| n.get());
+                            println!("\nType how many threads each Ai can search with (this computer has {}):", cores);
+                            match TicTacToeGame::input_usize() {
+                                Some(n) if n > 0 => game.set_ai_threads(n),
+                                _ => {
+                                    println!("Invalid Input");
+                                    continue
+                                }
+                            }
+                            println!("\nWould you like the Ai to pick randomly between equally good moves? Only without it any amount of threads plays the same moves (Y/n)\n");
+                            game.set_ai_random_moves(TicTacToeGame::input_bool());
+                        } else if n == 6 {
+                            println!("\nType the number of the player whose Ai will use the weights (1 or 2):");
+                            let player_n = TicTacToeGame::input_usize();
+                            println!("\nType the path of the weights file:");
+                            match HeuristicWeights::load(TicTacToeGame::input_string()) {
+                                Ok(weights) => {
+                                    if !player_n.is_some_and(|n| game.set_ai_weights(n, weights)) {
+                                        println!("That player isn't an Ai!");
+                                        continue
+                                    }
+                                    println!("\nLoaded weights:\n{}\n", weights)
+                                }
+                                Err(e) => {
+                                    println!("{}", e);
+                                    continue
+                                }
+                            }
+                        } else if n == 7 {
+                            println!("\nType the seed for the Ai moves, the same seed replays the same game (Enter: a new seed every game):");
+                            game.seed = TicTacToeGame::input_usize().map(|n| n as u64);
+                        } else if n == 8 {
+                            println!("\nType how far from the filled squares the Ai looks for moves (0: the whole board):");
+                            if let Some(n) = TicTacToeGame::input_usize() {
+                                game.set_ai_candidate_radius(n);
+                            }else {
+                                println!("Invalid Input");
+                                continue
+                            }
+                        } else if n == 9 {
+                            println!("\nType the path of the opening book file (Enter: no book):");
+                            let path = TicTacToeGame::input_string();
+                            if path.is_empty() {
+                                game.set_ai_opening_book(None);
+                            }else {
+                                match OpeningBook::load(path, &game.board) {
+                                    Ok(book) if book.is_empty() => {
+                                        println!("The book has no positions!");
+                                        continue
+                                    }
+                                    Ok(book) => {
+                                        println!("\nLoaded {} positions for a {}x{} board with {} in a row", book.len(), book.x_size, book.y_size, book.seq_to_win);
+                                        game.set_ai_opening_book(Some(Arc::new(book)))
+                                    }
+                                    Err(e) => {
+                                        println!("{}", e);
+                                        continue
+                                    }
+                                }
+                            }
+                        } else if n == 10 {
+                            println!("\nType the path of the tablebase file (Enter: no tablebase):");
+                            let path = TicTacToeGame::input_string();
+                            if path.is_empty() {
+                                game.set_ai_tablebase(None);
+                            }else {
+                                match Tablebase::load(path) {
+                                    Ok(tablebase) if tablebase.is_empty() => {
+                                        println!("The tablebase has no positions!");
+                                        continue
+                                    }
+                                    Ok(tablebase) if !tablebase.fits(&game.board) => {
+                                        println!("The tablebase is for a {}x{} board with {} in a row, change the board size first!", tablebase.x_size, tablebase.y_size, tablebase.seq_to_win);
+                                        continue
+                                    }
+                                    Ok(tablebase) => {
+                                        println!("\nLoaded {} positions for a {}x{} board with {} in a row", tablebase.len(), tablebase.x_size, tablebase.y_size, tablebase.seq_to_win);
+                                        game.set_ai_tablebase(Some(Arc::new(tablebase)))
+                                    }
+                                    Err(e) => {
+                                        println!("{}", e);
+                                        continue
+                                    }
+                                }
+                            }
+                        } else if n == 11 {
+                            println!("\nType how many iterations the Monte Carlo Ai runs per move (0: no limit):");
+                            let iterations = match TicTacToeGame::input_usize() {
+                                Some(n) => (n > 0).then_some(n),
+                                None => {
+                                    println!("Invalid Input");
+                                    continue
+                                }
+                            };
+                            println!("\nType how long the Monte Carlo Ai searches per move, in ms (0: no limit):");
+                            let time_limit = match TicTacToeGame::input_usize() {
+                                Some(n) => (n > 0).then(|| Duration::from_millis(n as u64)),
+                                None => {
+                                    println!("Invalid Input");
+                                    continue
+                                }
+                            };
+                            if !game.set_mcts_limits(iterations, time_limit) {
+                                println!("There is no Monte Carlo Ai in this game!");
+                                continue
+                            }
+                            if iterations.is_none() && time_limit.is_none() {
+                                println!("\nWith no limits, the Monte Carlo Ai runs {} iterations per move", DEFAULT_ITERATIONS);
+                            }
+                        } else if n == 12 {
+                            println!("\nWould you like debug mode to show the nodes move ordering saves? Every Ai move gets searched twice (Y/n)\n");
+                            game.compare_move_ordering = TicTacToeGame::input_bool();
                         }
-                        println!("Board Size: {}\nSequence to win: {}\nPlayer 1 symbol: {}\nPlayer 2 symbol: {}", game.board.size(), game.board.seq_to_win, game.player1.square_symbol, game.player2.square_symbol);
+                        println!("Board Size: {}x{}\nSequence to win: {}\nPlayer 1 symbol: {}\nPlayer 2 symbol: {}", game.board.x_size, game.board.y_size, game.board.seq_to_win, game.player1.square_symbol, game.player2.square_symbol);
                     }else {
                         break
                     }
@@ -155,6 +316,11 @@ impl TicTacToeGame {
             None
         }
     }
+    fn input_string() -> String {
+        let mut ans: String = String::new();
+        stdin().read_line(&mut ans).unwrap();
+        ans.trim().to_string()
+    }
     fn input_bool() -> bool {
         let mut ans: String = String::new();
         stdin().read_line(&mut ans).unwrap();
@@ -167,66 +333,115 @@ impl TicTacToeGame {
     }
     pub fn load_default_1player_game(ai_difficulty: AiDifficulties) -> TicTacToeGame {
         let mut g: TicTacToeGame = Default::default();
-        g.player2.p_type = PlayerType::Computer(TicTacToeGame::create_ai(ai_difficulty, g.player2.square_symbol.clone(), g.player1.square_symbol.clone()));
+        g.player2.p_type = PlayerType::Computer(Box::new(TicTacToeGame::create_ai(ai_difficulty, g.player2.square_symbol, g.player1.square_symbol)));
         return g
     }
+    pub fn load_default_1player_mcts_game() -> TicTacToeGame {
+        let mut g: TicTacToeGame = Default::default();
+        g.player2.p_type = PlayerType::Mcts(Box::new(TicTacToeGame::create_mcts(g.player2.square_symbol, g.player1.square_symbol)));
+        g
+    }
+    pub fn load_default_ai_vs_mcts_game(ai_difficulty: AiDifficulties) -> TicTacToeGame {
+        let mut g: TicTacToeGame = TicTacToeGame::load_default_1player_mcts_game();
+        g.player1.p_type = PlayerType::Computer(Box::new(TicTacToeGame::create_ai(ai_difficulty, g.player1.square_symbol, g.player2.square_symbol)));
+        g.show_turn_info_mode = true;
+        g
+    }
     pub fn load_default_2player_game() -> TicTacToeGame {
         Default::default()
     }
     pub fn load_default_ai_game(ai1_difficulty: AiDifficulties, ai2_difficulty: AiDifficulties) -> TicTacToeGame {
         let mut g: TicTacToeGame = TicTacToeGame::load_default_1player_game(ai2_difficulty);
-        g.player1.p_type = PlayerType::Computer(TicTacToeGame::create_ai(ai1_difficulty, g.player1.square_symbol.clone(), g.player2.square_symbol.clone()));
+        g.player1.p_type = PlayerType::Computer(Box::new(TicTacToeGame::create_ai(ai1_difficulty, g.player1.square_symbol, g.player2.square_symbol)));
         g.show_turn_info_mode = true;
         return g
     }
     
+    fn create_mcts(symbol: char, op_symbol: char) -> MctsPlayer {
+        MctsPlayer::create(None, None, symbol, op_symbol, DEFAULT_SEED) // start_game gives it the seed of the game
+    }
     fn create_ai(difficulty: AiDifficulties, symbol: char, op_symbol: char) -> Ai {
         let max_childs: usize; // gets incremented every turn
         let max_layers: usize;
+        let threat_depth: usize; // 0 doesn't look for threat sequences
         match difficulty { // 0 == infinite
             AiDifficulties::Easy => {
                 max_childs = 7;
                 max_layers = 1;
+                threat_depth = 0;
             }
             AiDifficulties::Medium => {
                 max_childs = 6;
                 max_layers = 2;
+                threat_depth = 1;
             }
-            AiDifficulties::Hard => {
+            AiDifficulties::Hard | AiDifficulties::Perfect => {
                 max_childs = 10;
                 max_layers = 5;
+                threat_depth = DEFAULT_THREAT_DEPTH;
             }
         }
-        Ai::create(max_childs, max_layers, symbol, op_symbol)
+        let mut ai = Ai::create(max_childs, max_layers, symbol, op_symbol);
+        ai.set_threat_depth(threat_depth);
+        ai.set_exact_solver(matches!(difficulty, AiDifficulties::Perfect));
+        ai
     }
     pub fn start_game(&mut self) {
         let mut turn_logger= TurnLogger::start();
+        let seed = self.seed.unwrap_or_else(|| thread_rng().gen());
+        self.set_ai_seeds(seed);
+        self.set_ai_move_ordering_comparison(self.show_turn_info_mode && self.compare_move_ordering);
+        if self.show_turn_info_mode {
+            println!("Seed: {}", seed);
+        }
         loop {
             let mut give_delay = false;
             match &mut self.game_state {
                 GameState::Begin(p) => {
-                    match p {
-                        1 => self.game_state = GameState::Player(p.to_owned(), self.player1.clone()),
-                        2 => self.game_state = GameState::Player(p.to_owned(), self.player2.clone()),
-                        _ => {}
+                    if *p == 1 || *p == 2 {
+                        self.game_state = GameState::Player(*p)
                     }
                     println!("{}", self);
                 }
-                GameState::Player(n, p) => {
+                GameState::Player(n) => {
+                    let n = *n;
+                    let (name, symbol) = (self.player(n).name.clone(), self.player(n).square_symbol);
                     let col: usize;
                     let line: usize;
+                    let mut ai_stats = None;
                     turn_logger.restart_timer();
-                    match &mut p.p_type {
+                    let p_type = if n == 1 { &mut self.player1.p_type } else { &mut self.player2.p_type };
+                    match p_type {
                         PlayerType::Human => {
                             let col_input;
                             let line_input;
-                            println!("{}'s turn, type the column of your next move\ncolumn: ", p.name);
+                            let op_symbol = if symbol == self.player1.square_symbol { self.player2.square_symbol } else { self.player1.square_symbol };
+                            TicTacToeGame::show_dangers(&self.board, op_symbol);
+                            println!("{}'s turn, type the column of your next move (u: undo, r: redo, h: hint)\ncolumn (1-{}): ", name, self.board.x_size);
                             let mut ans: String = String::new();
                             stdout().flush().expect("");
                             stdin().read_line(&mut ans).unwrap();
                             ans.remove(ans.len()-1);
+                            if ans == "h" {
+                                self.show_hints(symbol);
+                                continue
+                            }
+                            if ans == "u" || ans == "r" {
+                                let current_player = n;
+                                let changed = if ans == "u" {
+                                    self.take_back_turn(current_player, &mut turn_logger)
+                                }else {
+                                    self.redo_turn(current_player, &mut turn_logger)
+                                };
+                                if !changed {
+                                    println!("\nNothing to {}!", if ans == "u" { "undo" } else { "redo" });
+                                }else if !self.board.is_finished() {
+                                    println!("{}", self);
+                                }
+                                continue
+                            }
                             col_input = ans.parse();
-                            println!("\nline: ");
+                            println!("\nline (1-{}): ", self.board.y_size);
                             ans.clear();
                             stdout().flush().expect("");
                             stdin().read_line(&mut ans).unwrap();
@@ -235,7 +450,7 @@ impl TicTacToeGame {
                             if col_input.is_ok() && line_input.is_ok() {
                                 col = col_input.unwrap();
                                 line = line_input.unwrap();
-                                if col < 1 || col > self.board.x_size || line < 1 || line > self.board.y_size {
+                                if col < 1 || line < 1 {
                                     println!("Invalid column or line number");
                                     continue
                                 }
@@ -245,31 +460,48 @@ impl TicTacToeGame {
                             }
                         }
                         PlayerType::Computer(ai) => {
-                            let ai_action = ai.act(self.board.clone());
+                            let ai_action = ai.act(self.board.clone(), self.ai_time_budget);
                             col = ai_action.0 + 1;
                             line = ai_action.1 + 1;
+                            ai_stats = Some(ai.last_search_stats().clone());
+                            give_delay = true;
+                        }
+                        PlayerType::Mcts(mcts) => {
+                            let mcts_action = mcts.act(self.board.clone(), self.ai_time_budget);
+                            col = mcts_action.0 + 1;
+                            line = mcts_action.1 + 1;
+                            ai_stats = Some(mcts.last_search_stats().clone());
                             give_delay = true;
                         }
                     }
+                    let board_state = match self.board.set_square(col - 1, line - 1, SquareState::Filled(symbol)) {
+                        Ok(state) => state,
+                        Err(MoveError::GameFinished) => {
+                            self.game_state = GameState::Finished;
+                            continue
+                        }
+                        Err(e) => {
+                            println!("\n{} Type a valid position!", e);
+                            continue
+                        }
+                    };
                     turn_logger.end_timer();
+                    turn_logger.ai_stats = ai_stats;
                     turn_logger.total_turns += 1;
                     turn_logger.latest_placed_coord = (col, line);
-                    turn_logger.player_n_turn = n.clone();
-                    let board_state = self.board.set_square(col - 1, line - 1, SquareState::Filled(p.square_symbol));
+                    turn_logger.player_n_turn = n;
                     match board_state {
                         TurnState::Draw => {
                             println!("\nAll spaces have been filled! It's a draw!");
                             self.game_state = GameState::Finished
                         }
-                        TurnState::Error => {
-                            println!("\nType a valid position!")
-                        }
-                        TurnState::Victory => {
-                            println!("\n{} in a row! {} wins!", self.board.seq_to_win, p.name);
+                        TurnState::Victory(ref line) => {
+                            let (start, end) = (line.start(), line.end());
+                            println!("\n{} in a row! {} wins! ({} line from x={}; y={} to x={}; y={})", line.squares.len(), name, line.direction, start.0 + 1, start.1 + 1, end.0 + 1, end.1 + 1);
                             self.game_state = GameState::Finished
                         }
                         _ => {
-                            if n.to_owned().eq(&1) {
+                            if n == 1 {
                                 self.set_current_player_to_2()
                             }else {
                                 self.set_current_player_to_1()
@@ -293,33 +525,215 @@ impl TicTacToeGame {
             }
         }
     }
+    fn player(&self, n: usize) -> &Player {
+        if n == 1 { &self.player1 } else { &self.player2 }
+    }
+    fn moves_until_human_turn(&self, current_player: usize) -> usize {
+        // against an Ai the human's last move is 2 moves back, since the Ai's reply comes after it
+        let other_player = if current_player == 1 { &self.player2 } else { &self.player1 };
+        if let PlayerType::Human = other_player.p_type { 1 } else { 2 }
+    }
+    fn take_back_turn(&mut self, current_player: usize, turn_logger: &mut TurnLogger) -> bool {
+        let moves = self.moves_until_human_turn(current_player);
+        if self.board.history().len() < moves {
+            return false
+        }
+        for _ in 0..moves {
+            self.board.undo();
+        }
+        turn_logger.total_turns -= moves;
+        if moves == 1 {
+            self.switch_current_player(current_player)
+        }
+        true
+    }
+    fn redo_turn(&mut self, current_player: usize, turn_logger: &mut TurnLogger) -> bool {
+        let moves = self.moves_until_human_turn(current_player).min(self.board.redoable_moves());
+        if moves == 0 {
+            return false
+        }
+        let mut next_player = current_player;
+        for _ in 0..moves {
+            let state = self.board.redo().unwrap();
+            turn_logger.total_turns += 1;
+            next_player = if next_player == 1 { 2 } else { 1 };
+            if state != TurnState::Continue {
+                self.game_state = GameState::Finished;
+                return true
+            }
+        }
+        if next_player != current_player {
+            self.switch_current_player(current_player)
+        }
+        true
+    }
+    fn switch_current_player(&mut self, current_player: usize) {
+        if current_player == 1 {
+            self.set_current_player_to_2()
+        }else {
+            self.set_current_player_to_1()
+        }
+    }
     fn set_current_player_to_1(&mut self) {
-        self.game_state = GameState::Player(1, self.player1.clone());
+        self.game_state = GameState::Player(1);
     }
     fn set_current_player_to_2(&mut self) {
-        self.game_state = GameState::Player(2, self.player2.clone());
+        self.game_state = GameState::Player(2);
     }
     pub fn set_empty_space_symbol(&mut self, symbol: char) {
         self.board.empty_space_symbol = symbol;
     }
     pub fn set_player1_symbol(&mut self, symbol: char) {
         self.player1.square_symbol = symbol;
+        self.update_computer_symbols()
+    }
+    pub fn set_player2_symbol(&mut self, symbol: char) {
+        self.player2.square_symbol = symbol;
+        self.update_computer_symbols()
+    }
+    fn update_computer_symbols(&mut self) {
+        let (symbol1, symbol2) = (self.player1.square_symbol, self.player2.square_symbol);
+        for (p_type, symbol, op_symbol) in [(&mut self.player1.p_type, symbol1, symbol2), (&mut self.player2.p_type, symbol2, symbol1)] {
+            match p_type {
+                PlayerType::Computer(ai) => {
+                    ai.symbol = symbol;
+                    ai.op_symbol = op_symbol
+                }
+                PlayerType::Mcts(mcts) => {
+                    mcts.symbol = symbol;
+                    mcts.op_symbol = op_symbol
+                }
+                PlayerType::Human => {}
+            }
+        }
+    }
+    fn show_hints(&self, symbol: char) {
+        let op_symbol = if symbol == self.player1.square_symbol { self.player2.square_symbol } else { self.player1.square_symbol };
+        let mut ai = TicTacToeGame::create_ai(AiDifficulties::Hard, symbol, op_symbol);
+        ai.set_seed(self.seed.unwrap_or(DEFAULT_SEED)); // the same position always gets the same hints
+        let hints = ai.rank_moves(&self.board, HINT_AMOUNT, self.ai_time_budget);
+        let marks: Vec<((usize, usize), char)> = hints.iter().enumerate().map(|(i, (coord, _))| (*coord, char::from_digit(i as u32 + 1, 10).unwrap())).collect();
+        println!("\nSuggested moves:\n{}", self.board.render(&marks));
+        for (i, ((x, y), score)) in hints.iter().enumerate() {
+            match Ai::score_to_game_value(*score) {
+                Some(value) => println!("{}: column {}, line {} ({})", i + 1, x + 1, y + 1, value),
+                None => println!("{}: column {}, line {} (score: {})", i + 1, x + 1, y + 1, score)
+            }
+        }
+        println!();
+    }
+    // the opponent's lines one move from winning, open lines and forks, so the human doesn't miss them
+    fn show_dangers(board: &TicTacToe, op_symbol: char) {
+        let mut winning_squares: Vec<(usize, usize)> = board.lines_one_move_from_winning(op_symbol).into_iter().flat_map(|line| line.empty_squares).collect();
+        winning_squares.sort_unstable();
+        winning_squares.dedup();
+        let open_lines = board.open_lines(op_symbol);
+        let forks: Vec<(usize, usize)> = board.fork_squares(op_symbol).into_iter().map(|i| board.get_index_coord(i)).collect();
+        if winning_squares.is_empty() && forks.is_empty() {
+            return
+        }
+        let marks: Vec<((usize, usize), char)> = winning_squares.iter().chain(forks.iter()).map(|coord| (*coord, '!')).collect();
+        println!("\nDanger!\n{}", board.render(&marks));
+        for (x, y) in winning_squares.iter() {
+            println!("{} wins next turn on column {}, line {}", op_symbol, x + 1, y + 1);
+        }
+        for line in open_lines.iter() {
+            let (start, end) = (line.squares[0], line.squares[line.squares.len() - 1]);
+            println!("{} has an open {} line from x={}; y={} to x={}; y={}, blocking one end won't stop it", op_symbol, line.direction, start.0 + 1, start.1 + 1, end.0 + 1, end.1 + 1);
+        }
+        for (x, y) in forks.iter() {
+            println!("{} makes a fork on column {}, line {}", op_symbol, x + 1, y + 1);
+        }
+        println!();
+    }
+    pub fn set_mcts_limits(&mut self, iterations: Option<usize>, time_limit: Option<Duration>) -> bool { // false if no player uses Monte Carlo
+        let mut found = false;
+        for player in [&mut self.player1, &mut self.player2] {
+            if let PlayerType::Mcts(mcts) = &mut player.p_type {
+                mcts.set_limits(iterations, time_limit);
+                found = true
+            }
+        }
+        found
+    }
+    pub fn set_ai_memory(&mut self, bytes: usize) {
         if let PlayerType::Computer(ai) = &mut self.player1.p_type {
-            ai.symbol = symbol
+            ai.set_transposition_table_memory(bytes)
         }
         if let PlayerType::Computer(ai) = &mut self.player2.p_type {
-            ai.op_symbol = symbol
+            ai.set_transposition_table_memory(bytes)
         }
     }
-    pub fn set_player2_symbol(&mut self, symbol: char) {
-        self.player2.square_symbol = symbol;
+    fn set_ai_seeds(&mut self, seed: u64) {
+        for (n, player) in [&mut self.player1, &mut self.player2].into_iter().enumerate() {
+            let player_seed = seed.wrapping_add(n as u64); // so both players don't share their randomness
+            match &mut player.p_type {
+                PlayerType::Computer(ai) => ai.set_seed(player_seed),
+                PlayerType::Mcts(mcts) => mcts.set_seed(player_seed),
+                PlayerType::Human => {}
+            }
+        }
+    }
+    fn set_ai_move_ordering_comparison(&mut self, compare: bool) { // only worth the extra search when it gets shown
+        if let PlayerType::Computer(ai) = &mut self.player1.p_type {
+            ai.set_compare_move_ordering(compare)
+        }
         if let PlayerType::Computer(ai) = &mut self.player2.p_type {
-            ai.symbol = symbol
+            ai.set_compare_move_ordering(compare)
         }
+    }
+    pub fn set_ai_candidate_radius(&mut self, radius: usize) {
         if let PlayerType::Computer(ai) = &mut self.player1.p_type {
-            ai.op_symbol = symbol
+            ai.set_candidate_radius(radius)
+        }
+        if let PlayerType::Computer(ai) = &mut self.player2.p_type {
+            ai.set_candidate_radius(radius)
         }
     }
+    pub fn set_ai_opening_book(&mut self, book: Option<Arc<OpeningBook>>) { // only used while the board matches it
+        if let PlayerType::Computer(ai) = &mut self.player1.p_type {
+            ai.set_opening_book(book.clone())
+        }
+        if let PlayerType::Computer(ai) = &mut self.player2.p_type {
+            ai.set_opening_book(book)
+        }
+    }
+    pub fn set_ai_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) { // only used while the board matches it
+        if let PlayerType::Computer(ai) = &mut self.player1.p_type {
+            ai.set_tablebase(tablebase.clone())
+        }
+        if let PlayerType::Computer(ai) = &mut self.player2.p_type {
+            ai.set_tablebase(tablebase)
+        }
+    }
+    pub fn set_ai_threads(&mut self, threads: usize) {
+        if let PlayerType::Computer(ai) = &mut self.player1.p_type {
+            ai.set_threads(threads)
+        }
+        if let PlayerType::Computer(ai) = &mut self.player2.p_type {
+            ai.set_threads(threads)
+        }
+    }
+    pub fn set_ai_random_moves(&mut self, random_moves: bool) {
+        if let PlayerType::Computer(ai) = &mut self.player1.p_type {
+            ai.set_random_moves(random_moves)
+        }
+        if let PlayerType::Computer(ai) = &mut self.player2.p_type {
+            ai.set_random_moves(random_moves)
+        }
+    }
+    pub fn set_ai_weights(&mut self, player_n: usize, weights: HeuristicWeights) -> bool { // false if the player doesn't use them
+        let player = match player_n {
+            1 => &mut self.player1,
+            2 => &mut self.player2,
+            _ => return false
+        };
+        if let PlayerType::Computer(ai) = &mut player.p_type {
+            ai.set_heuristic_weights(weights);
+            return true
+        }
+        false
+    }
     pub fn set_first_player(&mut self, player_n: usize){
         if player_n > 0 && player_n < 3 {
             if let GameState::Begin(_) = self.game_state {
@@ -343,24 +757,17 @@ impl TicTacToeGame {
                 panic!("{} is not a valid player number!", player_n)
             }
         }
-        if let PlayerType::Computer(ai) = &mut self.player1.p_type {
-            ai.op_symbol = self.player2.square_symbol;
-            ai.symbol = self.player1.square_symbol
-        }
-        if let PlayerType::Computer(ai) = &mut self.player2.p_type {
-            ai.op_symbol = self.player1.square_symbol;
-            ai.symbol = self.player2.square_symbol
-        }
+        self.update_computer_symbols()
+    }
+    pub fn valid_size(x_size: usize, y_size: usize, in_a_row_to_win: usize) -> bool {
+        x_size > 0 && y_size > 0 && in_a_row_to_win > 0 && x_size.max(y_size) >= in_a_row_to_win && TicTacToe::fits_bitboard(x_size, y_size)
     }
-    pub fn change_size(&mut self, size: usize, in_a_row_to_win: usize) -> bool{
-        if size < in_a_row_to_win {
-            //panic!("Board size of {} is too small to make {} in a row!", size, in_a_row_to_win)
+    pub fn change_size(&mut self, x_size: usize, y_size: usize, in_a_row_to_win: usize) -> bool{
+        if !TicTacToeGame::valid_size(x_size, y_size, in_a_row_to_win) {
+            //panic!("Board size of {}x{} is too small to make {} in a row!", x_size, y_size, in_a_row_to_win)
             return false
         }
-        self.board.x_size = size;
-        self.board.y_size = size;
-        self.board.seq_to_win = in_a_row_to_win;
-        self.board.squares = vec![SquareState::None; size.pow(2)];
+        self.board = TicTacToe::new(x_size, y_size, in_a_row_to_win, self.board.empty_space_symbol);
         true
     }
     pub fn reload_game(&mut self) {
@@ -385,6 +792,9 @@ impl Default for TicTacToeGame {
             },
             game_state: GameState::Begin(1),
             show_turn_info_mode: false,
+            compare_move_ordering: false,
+            ai_time_budget: None,
+            seed: None,
         }
     }
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 use std::time::{Duration, Instant};
+use crate::game::ai::search_stats::SearchStats;
 use crate::game::tictactoe_core::TurnState;
 
 pub struct TurnLogger {
@@ -8,7 +9,8 @@ pub struct TurnLogger {
     pub player_n_turn: usize,
     pub latest_placed_coord: (usize, usize),
     pub game_state: TurnState,
-    pub total_turns: usize
+    pub total_turns: usize,
+    pub ai_stats: Option<SearchStats>
 }
 
 impl TurnLogger {
@@ -20,6 +22,7 @@ impl TurnLogger {
             latest_placed_coord: (0, 0),
             game_state: TurnState::Continue,
             total_turns: 0,
+            ai_stats: None,
         }
     }
     pub fn restart_timer(&mut self) {
@@ -35,7 +38,11 @@ impl TurnLogger {
 
 impl Display for TurnLogger {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Turn: {}\nPlaced coordinates: x={}; y={}\nGame State: {}\nElapsed Time: {}", self.total_turns, self.latest_placed_coord.0, self.latest_placed_coord.1, self.game_state, self.elapsed_time().as_secs_f64())
+        write!(f, "Turn: {}\nPlaced coordinates: x={}; y={}\nGame State: {}\nElapsed Time: {}", self.total_turns, self.latest_placed_coord.0, self.latest_placed_coord.1, self.game_state, self.elapsed_time().as_secs_f64())?;
+        if let Some(stats) = &self.ai_stats {
+            write!(f, "\n{}", stats)?;
+        }
+        Ok(())
     }
 }
 
@@ -1,10 +1,134 @@
+use std::env;
+use std::time::Instant;
+use rand::random;
+use crate::game::ai::heuristic_weights::HeuristicWeights;
+use crate::game::ai::opening_book::OpeningBook;
+use crate::game::ai::proof_number::{ProofNumberSearch, DEFAULT_MAX_NODES};
+use crate::game::ai::tablebase::Tablebase;
+use crate::game::ai::tuner::Tuner;
 use crate::game::tictactoe_game::{TicTacToeGame};
 mod game;
 
+const BOOK_USAGE: &str = "Usage: book <width> <height> <seq to win> [moves (default: 4)] [search depth (default: 7)] [output file (default: book.txt)] [seed (default: random)]";
+const PROVE_USAGE: &str = "Usage: prove <width> <height> <seq to win> <position> [max nodes (default: 2000000)]\n\
+    The position goes row by row, with x and o for the players and - for empty squares, x moves next when both have the same amount";
+const TABLEBASE_USAGE: &str = "Usage: tablebase <width> <height> <seq to win> [output file (default: tablebase.bin)], for boards of up to 16 squares";
+const TUNE_USAGE: &str = "Usage: tune <width> <height> <seq to win> [generations (default: 100)] [games per match (default: 100)] [output file] [seed (default: random)]";
+
 fn main() {
-    TicTacToeGame::start_new_game_prompts()
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("tune") => tune(&args[1..]),
+        Some("book") => book(&args[1..]),
+        Some("tablebase") => tablebase(&args[1..]),
+        Some("prove") => prove(&args[1..]),
+        _ => TicTacToeGame::start_new_game_prompts()
+    }
+}
+
+fn tune(args: &[String]) {
+    let numbers: Vec<Option<usize>> = args.iter().take(5).map(|arg| arg.parse().ok()).collect();
+    let (x_size, y_size, seq_to_win) = match numbers[..] {
+        [Some(x), Some(y), Some(seq), ..] if TicTacToeGame::valid_size(x, y, seq) => (x, y, seq),
+        _ => {
+            println!("{}", TUNE_USAGE);
+            return
+        }
+    };
+    let generations = numbers.get(3).copied().flatten().unwrap_or(100);
+    let seed = seed_arg(args.get(6));
+    let mut tuner = Tuner::new(x_size, y_size, seq_to_win, seed).unwrap();
+    tuner.games_per_match = numbers.get(4).copied().flatten().unwrap_or(100);
+    println!("Tuning the heuristic weights for a {}x{} board with {} in a row, {} generations of {} games, seed {}", x_size, y_size, seq_to_win, generations, tuner.games_per_match, seed);
+    let (weights, stats) = match tuner.tune(HeuristicWeights::default(), generations) {
+        Ok(result) => result,
+        Err(e) => {
+            println!("Tuning stopped, an Ai made an illegal move: {}", e);
+            return
+        }
+    };
+    println!("\nBest weights found:\n{}\n\nAgainst the default weights: {}", weights, stats);
+    if let Some(path) = args.get(5) {
+        match weights.save(path) {
+            Ok(()) => println!("Saved to {}", path),
+            Err(e) => println!("{}", e)
+        }
+    }
 }
 
+fn book(args: &[String]) {
+    let numbers: Vec<Option<usize>> = args.iter().take(5).map(|arg| arg.parse().ok()).collect();
+    let (x_size, y_size, seq_to_win) = match numbers[..] {
+        [Some(x), Some(y), Some(seq), ..] if TicTacToeGame::valid_size(x, y, seq) => (x, y, seq),
+        _ => {
+            println!("{}", BOOK_USAGE);
+            return
+        }
+    };
+    let plies = numbers.get(3).copied().flatten().unwrap_or(4);
+    let depth = numbers.get(4).copied().flatten().unwrap_or(7);
+    let path = args.get(5).map_or("book.txt", String::as_str);
+    let seed = seed_arg(args.get(6));
+    println!("Generating an opening book for a {}x{} board with {} in a row, {} moves searched {} moves deep, seed {}", x_size, y_size, seq_to_win, plies, depth, seed);
+    let book = OpeningBook::generate(x_size, y_size, seq_to_win, plies, depth, seed);
+    match book.save(path) {
+        Ok(()) => println!("\nSaved {} positions to {}", book.len(), path),
+        Err(e) => println!("{}", e)
+    }
+}
 
+fn seed_arg(arg: Option<&String>) -> u64 { // a new seed when none is given, printed so the run can be repeated
+    arg.and_then(|arg| arg.parse().ok()).unwrap_or_else(random)
+}
 
+fn tablebase(args: &[String]) {
+    let numbers: Vec<Option<usize>> = args.iter().take(3).map(|arg| arg.parse().ok()).collect();
+    let (x_size, y_size, seq_to_win) = match numbers[..] {
+        [Some(x), Some(y), Some(seq)] if TicTacToeGame::valid_size(x, y, seq) && Tablebase::fits_board(x, y) => (x, y, seq),
+        _ => {
+            println!("{}", TABLEBASE_USAGE);
+            return
+        }
+    };
+    let path = args.get(3).map_or("tablebase.bin", String::as_str);
+    println!("Generating the tablebase for a {}x{} board with {} in a row", x_size, y_size, seq_to_win);
+    let tablebase = Tablebase::generate(x_size, y_size, seq_to_win).unwrap();
+    println!("\nFirst player: {}", tablebase.empty_board_value().unwrap());
+    match tablebase.save(path) {
+        Ok(()) => println!("Saved {} positions to {}", tablebase.len(), path),
+        Err(e) => println!("{}", e)
+    }
+}
 
+fn prove(args: &[String]) {
+    let numbers: Vec<Option<usize>> = args.iter().take(3).map(|arg| arg.parse().ok()).collect();
+    let position = match (&numbers[..], args.get(3)) {
+        ([Some(x), Some(y), Some(seq)], Some(cells)) if TicTacToeGame::valid_size(*x, *y, *seq) => {
+            ProofNumberSearch::position_from_cells(*x, *y, *seq, cells)
+        }
+        _ => {
+            println!("{}", PROVE_USAGE);
+            return
+        }
+    };
+    let Some((board, symbol, op_symbol)) = position else {
+        println!("That position isn't valid, or it's already finished");
+        return
+    };
+    let max_nodes = args.get(4).and_then(|arg| arg.parse().ok()).unwrap_or(DEFAULT_MAX_NODES);
+    let mut search = ProofNumberSearch::new(max_nodes);
+    let start = Instant::now();
+    let (result, main_line) = search.prove(&board, symbol, op_symbol);
+    // the first moves of the main line get numbered on the board
+    let marks: Vec<((usize, usize), char)> = main_line.iter().take(9).enumerate()
+        .map(|(n, index)| (board.get_index_coord(*index), char::from_digit(n as u32 + 1, 10).unwrap())).collect();
+    println!("{}\n\n{} to move: {}\nNodes: {}\nSearch Time: {}", board.render(&marks), symbol, result, search.nodes, start.elapsed().as_secs_f64());
+    if !main_line.is_empty() {
+        print!("Main Line:");
+        for index in main_line {
+            let (x, y) = board.get_index_coord(index);
+            print!(" ({}, {})", x + 1, y + 1);
+        }
+        println!();
+    }
+}
\ No newline at end of file